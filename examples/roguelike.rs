@@ -1,7 +1,6 @@
-use std::{collections::HashMap, f32::consts::PI, time::Instant};
+use std::{collections::HashMap, time::Instant};
 
 use endlessgrid::*;
-use ::glam::Vec2;
 use macroquad::{prelude::*};
 use slotmap::{DefaultKey, SlotMap};
 use tiled::Loader;
@@ -146,28 +145,12 @@ async fn main() {
         };
         set_camera(&camera);
 
-        let mut tiles = HashMap::new();
-        let r = 512;
-        for a in 0..r {
-            let a =  a as f32 / r as f32 * PI * 2.0;
-            let start = Vec2::new(player_pos.0 as f32 + 0.5, player_pos.1 as f32 + 0.5);
-            let v = Vec2::new(a.cos(), a.sin());
-            let end = v * view_distance + start;
-
-            grid.cast_ray(start, end, |x|{
-                tiles.insert(x.index, ());
-                if x.cell.solid {
-                    return false;
-                }
-
-                true
-            });
-        }
+        let tiles = grid.field_of_view(player_pos, view_distance as u32, |x|x.solid);
 
         for (x,y) in tiles.keys() {
-            let Some(tile) = grid.get((*x,*y)) else {continue;};
-            let x = *x as f32 * tile_size_px;
-            let y = *y as f32 * tile_size_px;
+            let Some(tile) = grid.get((x,y)) else {continue;};
+            let x = x as f32 * tile_size_px;
+            let y = y as f32 * tile_size_px;
             draw_atlas(&tilemap_texture, x, y, tile.index as f32, WHITE, tile_size_px);
             for entity in tile.entities.keys() {
                 let Some(entity) = entities.get(*entity) else { continue;};
@@ -0,0 +1,38 @@
+use crate::Grid;
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Gets the top left and bottom right coordinate of the occupied chunks, or `None` if the grid is empty
+    ///
+    /// Chunk coordinates are cell indices divided by the chunk size, i.e. chunk `(0, 0)` holds cell `(0, 0)`
+    pub fn chunk_bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+        self.chunks.values()
+            .filter(|chunk|!chunk.is_empty())
+            .map(|chunk|chunk.coord())
+            .fold(None, |bounds, p| match bounds {
+                None => Some((p, p)),
+                Some((min, max)) => Some(((p.0.min(min.0), p.1.min(min.1)), (p.0.max(max.0), p.1.max(max.1)))),
+            })
+    }
+
+    /// Gets the top left and bottom right index of the occupied cells, or `None` if the grid is empty
+    ///
+    /// Only chunks on the edge of `chunk_bounds` are inspected, each of which caches its own bounds
+    pub fn bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+        let (min, max) = self.chunk_bounds()?;
+        let mut bounds:Option<((i32, i32), (i32, i32))> = None;
+        for chunk in self.chunks.values() {
+            let p = chunk.coord();
+            if p.0 != min.0 && p.0 != max.0 && p.1 != min.1 && p.1 != max.1 {
+                continue;
+            }
+            let Some((cmin, cmax)) = chunk.bounds() else {
+                continue;
+            };
+            bounds = Some(match bounds {
+                None => (cmin, cmax),
+                Some((min, max)) => ((cmin.0.min(min.0), cmin.1.min(min.1)), (cmax.0.max(max.0), cmax.1.max(max.1))),
+            });
+        }
+        bounds
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::{HashMap, HashSet};
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+use crate::{chunks_at_index, serialize_generated, Chunk, ChunkIndex, Grid, Storage};
+
+/// Serializes a `Chunk` or `Grid` in its compact form, storing uniform and palette chunks as their distinct values
+/// instead of a value per cell, see `Grid::fill_chunk` and `Grid::compact_chunk`
+///
+/// Serialize `Compact(&chunk)` or `Compact(&grid)` and deserialize `Compact<Chunk<T, N>>` or `Compact<Grid<T, N>>`.
+/// Deserializing the compact form requires `T: Clone`, as the cells of uniform and palette chunks share their values.
+/// The compact form differs from the default form, data must be read back the same way it was written
+pub struct Compact<G>(pub G);
+
+impl<T:Serialize, const N: usize> Serialize for Compact<&Chunk<T, N>> {
+    fn serialize<S:Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Chunk", 4)?;
+        state.serialize_field("index", &self.0.index)?;
+        state.serialize_field("len", &self.0.len)?;
+        state.serialize_field("inner", &self.0.inner)?;
+        state.serialize_field("storage", &self.0.storage)?;
+        state.end()
+    }
+}
+
+/// A deserialized compact `Chunk` which is validated before it is used, see `Chunk::from_parts`
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Clone"))]
+struct CompactChunkData<T> {
+    index:ChunkIndex,
+    len:usize,
+    inner:Vec<Option<T>>,
+    #[serde(default)]
+    storage:Storage<T>,
+}
+
+impl<'de, T:Deserialize<'de> + Clone, const N: usize> Deserialize<'de> for Compact<Chunk<T, N>> {
+    fn deserialize<D:Deserializer<'de>>(deserializer:D) -> Result<Self, D::Error> {
+        let CompactChunkData { index, len, inner, storage } = CompactChunkData::deserialize(deserializer)?;
+        Chunk::from_parts(index, len, inner, storage).map(Compact).map_err(D::Error::custom)
+    }
+}
+
+/// Chunks are serialized in `ChunkIndex` order, as by the default form of `Grid`
+impl<T:Serialize, const N: usize> Serialize for Compact<&Grid<T, N>> {
+    fn serialize<S:Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Grid", 2)?;
+        state.serialize_field("chunks", &CompactChunks(self.0))?;
+        state.serialize_field("generated", &Generated(&self.0.generated))?;
+        state.end()
+    }
+}
+
+/// Serializes the chunks of a grid in their compact form
+struct CompactChunks<'a, T, const N: usize>(&'a Grid<T, N>);
+
+impl<T:Serialize, const N: usize> Serialize for CompactChunks<'_, T, N> {
+    fn serialize<S:Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> {
+        let mut chunks:Vec<_> = self.0.chunks.iter().map(|(chunk_index, chunk)|(*chunk_index, Compact(&**chunk))).collect();
+        chunks.sort_unstable_by_key(|(chunk_index, _)|*chunk_index);
+        serializer.collect_map(chunks)
+    }
+}
+
+/// Serializes the generated chunks of a grid as by the default form
+struct Generated<'a>(&'a HashSet<ChunkIndex>);
+
+impl Serialize for Generated<'_> {
+    fn serialize<S:Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> {
+        serialize_generated(self.0, serializer)
+    }
+}
+
+/// A deserialized compact `Grid` whose chunks are validated as by the default form
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Clone"))]
+struct CompactGridData<T, const N: usize> {
+    chunks:HashMap<ChunkIndex, Compact<Chunk<T, N>>>,
+    #[serde(default)]
+    generated:HashSet<ChunkIndex>,
+}
+
+impl<'de, T:Deserialize<'de> + Clone, const N: usize> Deserialize<'de> for Compact<Grid<T, N>> {
+    fn deserialize<D:Deserializer<'de>>(deserializer:D) -> Result<Self, D::Error> {
+        let data = CompactGridData::<T, N>::deserialize(deserializer)?;
+        let chunks = chunks_at_index(data.chunks.into_iter().map(|(chunk_index, Compact(chunk))|(chunk_index, chunk)).collect());
+        Ok(Compact(Grid { chunks:chunks.map_err(D::Error::custom)?, generated:data.generated, ..Default::default() }))
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+use crate::{Chunk, ChunkIndex, Grid, CHUNK_SIZE};
+
+/// Dirty flags of the chunks of a `Grid`, and the chunks not yet saved if the grid is owned by a `StreamingGrid`
+#[derive(Clone, Default)]
+pub(crate) struct DirtyChunks {
+    /// Chunks modified since the dirty flags were last cleared by the user
+    pub(crate) chunks:HashSet<ChunkIndex>,
+    /// Chunks modified since they were last saved, only tracked by `StreamingGrid` independently of the dirty flags
+    pub(crate) unsaved:Option<HashSet<ChunkIndex>>,
+}
+
+impl DirtyChunks {
+    pub(crate) fn insert(&mut self, chunk_index:ChunkIndex) {
+        self.chunks.insert(chunk_index);
+        if let Some(unsaved) = &mut self.unsaved {
+            unsaved.insert(chunk_index);
+        }
+    }
+
+    pub(crate) fn extend(&mut self, chunk_indices:impl IntoIterator<Item = ChunkIndex>) {
+        for chunk_index in chunk_indices {
+            self.insert(chunk_index);
+        }
+    }
+}
+
+/// Chunks changed since the dirty flags were last cleared, created by `Grid::take_changes`
+#[derive(Clone)]
+pub struct ChangeSet<T, const N: usize = CHUNK_SIZE> {
+    /// Copies of the changed chunks still present in the grid, ordered by their `ChunkIndex`
+    pub chunks:Vec<Chunk<T, N>>,
+
+    /// Coordinates of the changed chunks no longer present in the grid, ordered by their `ChunkIndex`
+    pub removed:Vec<(i32, i32)>,
+}
+
+impl<T, const N: usize> ChangeSet<T, N> {
+    /// Returns `true` if nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.removed.is_empty()
+    }
+
+    /// Applies the changes to `grid`, e.g. to replicate the grid the changes were taken from
+    pub fn apply(self, grid:&mut Grid<T, N>) {
+        for coord in self.removed {
+            grid.remove_chunk(coord);
+        }
+        for chunk in self.chunks {
+            grid.insert_chunk(chunk);
+        }
+    }
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Gets the coordinates of all chunks modified since the dirty flags were last cleared, in row-major order
+    ///
+    /// Chunks are marked dirty by `insert`, `remove`, any mutable access such as `get_mut`, a mutable access through an `Entry` or `iter_mut`,
+    /// and by inserting or removing whole chunks, even if no cell actually changed.
+    /// Dirty flags are not serialized.
+    pub fn dirty_chunks(&self) -> Vec<(i32, i32)> {
+        let mut dirty:Vec<ChunkIndex> = self.dirty.chunks.iter().copied().collect();
+        dirty.sort_unstable();
+        dirty.into_iter().map(|chunk_index|chunk_index.coord::<N>()).collect()
+    }
+
+    /// Returns `true` if the chunk at the chunk coordinate was modified since the dirty flags were last cleared
+    pub fn is_chunk_dirty(&self, coord:impl Into<(i32, i32)>) -> bool {
+        ChunkIndex::from_coord::<N>(coord).is_some_and(|chunk_index|self.dirty.chunks.contains(&chunk_index))
+    }
+
+    /// Clears the dirty flags of all chunks
+    pub fn clear_dirty(&mut self) {
+        self.dirty.chunks.clear();
+    }
+}
+
+impl<T:Clone, const N: usize> Grid<T, N> {
+    /// Collects the changes since the dirty flags were last cleared and clears the dirty flags
+    pub fn take_changes(&mut self) -> ChangeSet<T, N> {
+        let mut dirty:Vec<ChunkIndex> = self.dirty.chunks.drain().collect();
+        dirty.sort_unstable();
+        let mut changes = ChangeSet { chunks:Vec::new(), removed:Vec::new() };
+        for chunk_index in dirty {
+            match self.chunks.get(&chunk_index) {
+                Some(chunk) => changes.chunks.push(Chunk::clone(chunk)),
+                None => changes.removed.push(chunk_index.coord::<N>()),
+            }
+        }
+        changes
+    }
+}
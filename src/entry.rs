@@ -0,0 +1,147 @@
+use std::{collections::hash_map, sync::Arc};
+use crate::{make_mut, Chunk, ChunkIndex, DirtyChunks, Grid, Index, CHUNK_SIZE};
+
+/// A view into a single cell of a `Grid`, which may either be vacant or occupied
+///
+/// Constructed using `Grid::entry`
+pub enum Entry<'a, T, const N: usize = CHUNK_SIZE> {
+    Occupied(OccupiedEntry<'a, T, N>),
+    Vacant(VacantEntry<'a, T, N>),
+}
+
+/// A view into an occupied cell of a `Grid`
+pub struct OccupiedEntry<'a, T, const N: usize = CHUNK_SIZE> {
+    index:(i32, i32),
+    local:usize,
+    chunk:hash_map::OccupiedEntry<'a, ChunkIndex, Arc<Chunk<T, N>>>,
+    dirty:&'a mut DirtyChunks,
+}
+
+/// A view into a vacant cell of a `Grid`
+pub struct VacantEntry<'a, T, const N: usize = CHUNK_SIZE> {
+    index:(i32, i32),
+    local:usize,
+    chunk:hash_map::Entry<'a, ChunkIndex, Arc<Chunk<T, N>>>,
+    dirty:&'a mut DirtyChunks,
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Gets the `Entry` of the cell at `index` for in-place manipulation
+    pub fn entry(&mut self, index: impl Into<(i32, i32)>) -> Entry<'_, T, N> {
+        let index:(i32, i32) = index.into();
+        let i:Index = index.into();
+        let chunk_index = i.chunk_index::<N>();
+        let local = i.local_index::<N>();
+        let dirty = &mut self.dirty;
+        match self.chunks.entry(chunk_index) {
+            hash_map::Entry::Occupied(chunk) if chunk.get().get_local(local).is_some_and(|x|x.is_some()) => {
+                Entry::Occupied(OccupiedEntry { index, local, chunk, dirty })
+            },
+            chunk => Entry::Vacant(VacantEntry { index, local, chunk, dirty }),
+        }
+    }
+}
+
+impl<'a, T, const N: usize> Entry<'a, T, N> {
+    /// Gets the index of the cell
+    pub fn index(&self) -> (i32, i32) {
+        match self {
+            Entry::Occupied(e) => e.index(),
+            Entry::Vacant(e) => e.index(),
+        }
+    }
+
+    /// Inserts `t` if the cell is vacant and returns a mutable reference to the cell
+    pub fn or_insert(self, t:T) -> &'a mut T {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(t),
+        }
+    }
+
+    /// Inserts the result of `f` if the cell is vacant and returns a mutable reference to the cell
+    pub fn or_insert_with<F:FnOnce() -> T>(self, f:F) -> &'a mut T {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(f()),
+        }
+    }
+
+    /// Calls `f` with the cell if it is occupied
+    pub fn and_modify<F:FnOnce(&mut T)>(mut self, f:F) -> Self {
+        if let Entry::Occupied(e) = &mut self {
+            f(e.get_mut());
+        }
+        self
+    }
+
+    /// Removes the cell, returning it if it was occupied
+    pub fn remove(self) -> Option<T> {
+        match self {
+            Entry::Occupied(e) => Some(e.remove()),
+            Entry::Vacant(_) => None,
+        }
+    }
+}
+
+impl<'a, T:Default, const N: usize> Entry<'a, T, N> {
+    /// Inserts `T::default()` if the cell is vacant and returns a mutable reference to the cell
+    pub fn or_default(self) -> &'a mut T {
+        self.or_insert_with(T::default)
+    }
+}
+
+impl<'a, T, const N: usize> OccupiedEntry<'a, T, N> {
+    /// Gets the index of the cell
+    pub fn index(&self) -> (i32, i32) {
+        self.index
+    }
+
+    /// Gets a reference to the cell
+    pub fn get(&self) -> &T {
+        self.chunk.get().get_local(self.local).and_then(|x|x.as_ref()).expect("cell should be occupied")
+    }
+
+    /// Gets a mutable reference to the cell
+    pub fn get_mut(&mut self) -> &mut T {
+        self.dirty.insert(*self.chunk.key());
+        make_mut(self.chunk.get_mut()).get_local_mut(self.local).expect("cell should be occupied")
+    }
+
+    /// Converts the entry into a mutable reference to the cell with the lifetime of the `Grid`
+    pub fn into_mut(self) -> &'a mut T {
+        self.dirty.insert(*self.chunk.key());
+        make_mut(self.chunk.into_mut()).get_local_mut(self.local).expect("cell should be occupied")
+    }
+
+    /// Replaces the cell with `t`, returning the previous value
+    pub fn insert(&mut self, t:T) -> T {
+        std::mem::replace(self.get_mut(), t)
+    }
+
+    /// Removes the cell from the `Grid`, removing the chunk as well if it becomes empty
+    pub fn remove(mut self) -> T {
+        self.dirty.insert(*self.chunk.key());
+        let t = make_mut(self.chunk.get_mut()).remove(self.local).expect("cell should be occupied");
+        if self.chunk.get().is_empty() {
+            self.chunk.remove();
+        }
+        t
+    }
+}
+
+impl<'a, T, const N: usize> VacantEntry<'a, T, N> {
+    /// Gets the index of the cell
+    pub fn index(&self) -> (i32, i32) {
+        self.index
+    }
+
+    /// Inserts `t` into the cell and returns a mutable reference to it
+    pub fn insert(self, t:T) -> &'a mut T {
+        self.dirty.insert(*self.chunk.key());
+        let chunk = self.chunk.or_insert_with_key(|chunk_index| Arc::new(Chunk { index:*chunk_index, ..Default::default() }));
+        let chunk = make_mut(chunk);
+        chunk.insert(self.local, t);
+        chunk.get_local_mut(self.local).expect("cell was just inserted")
+    }
+}
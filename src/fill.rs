@@ -0,0 +1,226 @@
+use std::{collections::{HashMap, VecDeque}, sync::Arc};
+use crate::{Chunk, ChunkIndex, Grid, CHUNK_SIZE};
+
+/// Which neighbours of a cell are considered connected to it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Connectivity {
+    /// The four orthogonal neighbours
+    #[default]
+    Four,
+
+    /// The four orthogonal and the four diagonal neighbours
+    Eight,
+}
+
+impl Connectivity {
+    fn offsets(&self) -> &'static [(i32, i32)] {
+        match self {
+            Connectivity::Four => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+            Connectivity::Eight => &[(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)],
+        }
+    }
+}
+
+/// A connected component found by `Grid::connected_components`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Component {
+    /// Number of cells in the component
+    pub size:usize,
+
+    /// Top left corner of the bounding box of the component
+    pub min:(i32, i32),
+
+    /// Bottom right corner of the bounding box of the component (inclusive)
+    pub max:(i32, i32),
+}
+
+/// Result of `Grid::connected_components`
+pub struct Components<const N: usize = CHUNK_SIZE> {
+    /// Label of each cell belonging to a component, which is the index of the component in `components`
+    pub labels:Grid<u32, N>,
+
+    /// The components ordered by their label
+    pub components:Vec<Component>,
+}
+
+/// Union-find over provisional labels
+struct Labels {
+    parents:Vec<u32>,
+}
+
+impl Labels {
+    fn create(&mut self) -> u32 {
+        let label = self.parents.len() as u32;
+        self.parents.push(label);
+        label
+    }
+
+    fn find(&mut self, mut label:u32) -> u32 {
+        while self.parents[label as usize] != label {
+            let parent = self.parents[self.parents[label as usize] as usize];
+            self.parents[label as usize] = parent;
+            label = parent;
+        }
+        label
+    }
+
+    fn union(&mut self, a:u32, b:u32) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[a.max(b) as usize] = a.min(b);
+        }
+    }
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Collects all cells connected to `start` for which `predicate` returns `true`
+    ///
+    /// Returns an empty grid if `start` is not present in the grid or does not satisfy `predicate`
+    pub fn flood_fill<F:Fn(&T)->bool>(&self, start:impl Into<(i32, i32)>, connectivity:Connectivity, predicate:F) -> Grid<(), N> {
+        let start:(i32, i32) = start.into();
+        let mut filled = Grid::default() as Grid<(), N>;
+        if !self.get(start).is_some_and(&predicate) {
+            return filled;
+        }
+        filled.insert(start, ());
+        let mut queue = VecDeque::from([start]);
+        while let Some((x, y)) = queue.pop_front() {
+            for (dx, dy) in connectivity.offsets() {
+                let (Some(nx), Some(ny)) = (x.checked_add(*dx), y.checked_add(*dy)) else {
+                    continue;
+                };
+                let index = (nx, ny);
+                if filled.get(index).is_none() && self.get(index).is_some_and(&predicate) {
+                    filled.insert(index, ());
+                    queue.push_back(index);
+                }
+            }
+        }
+        filled
+    }
+
+    /// Labels all groups of connected cells for which `predicate` returns `true`
+    ///
+    /// Cells are labeled chunk by chunk after which labels are merged across chunk borders
+    pub fn connected_components<F:Fn(&T)->bool>(&self, connectivity:Connectivity, predicate:F) -> Components<N> {
+        let eight = connectivity == Connectivity::Eight;
+        let mut labels = Labels { parents:Vec::new() };
+        let mut chunks:HashMap<ChunkIndex, Chunk<u32, N>> = HashMap::new();
+
+        // label cells within each chunk
+        for (chunk_index, chunk) in self.chunks.iter() {
+            if chunk.is_empty() {
+                continue;
+            }
+            let mut local:Vec<Option<u32>> = vec![None; N * N];
+            let mut len = 0;
+            for i in 0..N * N {
+                if !chunk.get_local(i).and_then(|x|x.as_ref()).is_some_and(&predicate) {
+                    continue;
+                }
+                let (x, y) = (i % N, i / N);
+                let mut neighbours = [None; 4];
+                if x > 0 {
+                    neighbours[0] = local[i - 1];
+                }
+                if y > 0 {
+                    neighbours[1] = local[i - N];
+                    if eight && x > 0 {
+                        neighbours[2] = local[i - N - 1];
+                    }
+                    if eight && x < N - 1 {
+                        neighbours[3] = local[i - N + 1];
+                    }
+                }
+                let mut label = None;
+                for neighbour in neighbours.into_iter().flatten() {
+                    match label {
+                        Some(label) => labels.union(label, neighbour),
+                        None => label = Some(neighbour),
+                    }
+                }
+                local[i] = Some(label.unwrap_or_else(|| labels.create()));
+                len += 1;
+            }
+            if len > 0 {
+                chunks.insert(*chunk_index, Chunk { index:*chunk_index, len, inner:local, ..Default::default() });
+            }
+        }
+
+        // merge labels across chunk borders
+        let label = |chunk:&Chunk<u32, N>, x:usize, y:usize| chunk.inner[y * N + x];
+        let neighbour = |chunk_index:&ChunkIndex, dx:i32, dy:i32| {
+            let x = chunk_index.x.checked_add_signed(dx)?;
+            let y = chunk_index.y.checked_add_signed(dy)?;
+            chunks.get(&ChunkIndex { x, y })
+        };
+        let mut pairs = Vec::new();
+        for (chunk_index, chunk) in chunks.iter() {
+            if let Some(right) = neighbour(chunk_index, 1, 0) {
+                for y in 0..N {
+                    pairs.push((label(chunk, N - 1, y), label(right, 0, y)));
+                    if eight && y > 0 {
+                        pairs.push((label(chunk, N - 1, y), label(right, 0, y - 1)));
+                    }
+                    if eight && y < N - 1 {
+                        pairs.push((label(chunk, N - 1, y), label(right, 0, y + 1)));
+                    }
+                }
+            }
+            if let Some(bottom) = neighbour(chunk_index, 0, 1) {
+                for x in 0..N {
+                    pairs.push((label(chunk, x, N - 1), label(bottom, x, 0)));
+                    if eight && x > 0 {
+                        pairs.push((label(chunk, x, N - 1), label(bottom, x - 1, 0)));
+                    }
+                    if eight && x < N - 1 {
+                        pairs.push((label(chunk, x, N - 1), label(bottom, x + 1, 0)));
+                    }
+                }
+            }
+            if !eight {
+                continue;
+            }
+            if let Some(bottom_right) = neighbour(chunk_index, 1, 1) {
+                pairs.push((label(chunk, N - 1, N - 1), label(bottom_right, 0, 0)));
+            }
+            if let Some(bottom_left) = neighbour(chunk_index, -1, 1) {
+                pairs.push((label(chunk, 0, N - 1), label(bottom_left, N - 1, 0)));
+            }
+        }
+        for (a, b) in pairs {
+            if let (Some(a), Some(b)) = (a, b) {
+                labels.union(a, b);
+            }
+        }
+
+        // resolve provisional labels into components
+        let mut ids:HashMap<u32, u32> = HashMap::new();
+        let mut components:Vec<Component> = Vec::new();
+        let mut ordered:Vec<_> = chunks.values_mut().collect();
+        ordered.sort_unstable_by_key(|chunk|chunk.index);
+        for chunk in ordered {
+            let top_left:(i32, i32) = chunk.index.index::<N>().into();
+            for (i, cell) in chunk.inner.iter_mut().enumerate() {
+                let Some(label) = cell else {
+                    continue;
+                };
+                let root = labels.find(*label);
+                let index = (top_left.0 + (i % N) as i32, top_left.1 + (i / N) as i32);
+                let id = *ids.entry(root).or_insert_with(|| {
+                    components.push(Component { size:0, min:index, max:index });
+                    components.len() as u32 - 1
+                });
+                let component = &mut components[id as usize];
+                component.size += 1;
+                component.min = (component.min.0.min(index.0), component.min.1.min(index.1));
+                component.max = (component.max.0.max(index.0), component.max.1.max(index.1));
+                *label = id;
+            }
+        }
+
+        let mut grid = Grid::default() as Grid<u32, N>;
+        grid.chunks = chunks.into_iter().map(|(chunk_index, chunk)|(chunk_index, Arc::new(chunk))).collect();
+        Components { labels:grid, components }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use crate::Grid;
+
+/// A row of a quadrant being scanned by `Grid::visit_field_of_view`
+///
+/// The slopes are stored as fractions with a positive denominator to keep the scan exact
+struct Row {
+    depth:i64,
+    start:(i64, i64),
+    end:(i64, i64),
+}
+
+impl Row {
+    /// Gets the first and last column of the row which lie between the start and end slope
+    fn columns(&self) -> (i64, i64) {
+        // round ties up and round ties down respectively
+        let min = (2 * self.depth * self.start.0 + self.start.1).div_euclid(2 * self.start.1);
+        let max = -(self.end.1 - 2 * self.depth * self.end.0).div_euclid(2 * self.end.1);
+        (min, max)
+    }
+
+    /// Returns `true` if the column lies within the slopes of the row, i.e. the origin can see the cell and the cell can see the origin
+    fn is_symmetric(&self, col:i64) -> bool {
+        col * self.start.1 >= self.depth * self.start.0 && col * self.end.1 <= self.depth * self.end.0
+    }
+
+    fn next(&self) -> Row {
+        Row { depth:self.depth + 1, start:self.start, end:self.end }
+    }
+}
+
+/// Transforms a row depth and column of a quadrant into a cell index relative to the origin
+type Transform = fn((i64, i64), i64, i64) -> (i64, i64);
+
+/// Gets the slope to the left edge of a column
+fn slope(depth:i64, col:i64) -> (i64, i64) {
+    (2 * col - 1, 2 * depth)
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Computes the field of view from `origin` using symmetric shadowcasting, calling `visit` for each visible cell
+    ///
+    /// Every visible cell is visited exactly once, cells on the diagonals through `origin` are shared by two quadrants and visited by the first which sees them.
+    /// Cells for which `is_opaque` returns `true` block the view but are visible themselves.
+    /// Cells not present in the grid block the view as well.
+    /// Only cells within `radius` of `origin` are visible.
+    pub fn visit_field_of_view<O:Fn(&T)->bool, F:FnMut((i32, i32), &T)>(&self, origin:impl Into<(i32, i32)>, radius:u32, is_opaque:O, mut visit:F) {
+        let origin:(i32, i32) = origin.into();
+        let Some(cell) = self.get(origin) else {
+            return;
+        };
+        visit(origin, cell);
+        let radius = radius as i64;
+        let transforms:[Transform; 4] = [
+            |o, depth, col| (o.0 + col, o.1 - depth),
+            |o, depth, col| (o.0 + col, o.1 + depth),
+            |o, depth, col| (o.0 + depth, o.1 + col),
+            |o, depth, col| (o.0 - depth, o.1 + col),
+        ];
+        let o = (origin.0 as i64, origin.1 as i64);
+        let mut diagonals:HashSet<(i32, i32)> = HashSet::new();
+        for transform in transforms {
+            let cell = |depth:i64, col:i64| {
+                let (x, y) = transform(o, depth, col);
+                let index = (i32::try_from(x).ok()?, i32::try_from(y).ok()?);
+                Some((index, self.get(index)?))
+            };
+            let is_wall = |cell:Option<((i32, i32), &T)>| cell.is_none_or(|(_, cell)|is_opaque(cell));
+            let mut rows = vec![Row { depth:1, start:(-1, 1), end:(1, 1) }];
+            while let Some(mut row) = rows.pop() {
+                if row.depth > radius {
+                    continue;
+                }
+                let (min, max) = row.columns();
+                let mut prev_wall = None;
+                for col in min..=max {
+                    let current = cell(row.depth, col);
+                    let wall = is_wall(current);
+                    if let Some((index, cell)) = current {
+                        let within = row.depth * row.depth + col * col <= radius * radius;
+                        let diagonal = col.abs() == row.depth;
+                        if within && (wall || row.is_symmetric(col)) && (!diagonal || diagonals.insert(index)) {
+                            visit(index, cell);
+                        }
+                    }
+                    if prev_wall == Some(true) && !wall {
+                        row.start = slope(row.depth, col);
+                    }
+                    if prev_wall == Some(false) && wall {
+                        let mut next = row.next();
+                        next.end = slope(row.depth, col);
+                        rows.push(next);
+                    }
+                    prev_wall = Some(wall);
+                }
+                if prev_wall == Some(false) {
+                    rows.push(row.next());
+                }
+            }
+        }
+    }
+
+    /// Computes the field of view from `origin` using symmetric shadowcasting, see `visit_field_of_view`
+    ///
+    /// Returns the visible cells as a `Grid`
+    pub fn field_of_view<O:Fn(&T)->bool>(&self, origin:impl Into<(i32, i32)>, radius:u32, is_opaque:O) -> Grid<(), N> {
+        let mut visible = Grid::default() as Grid<(), N>;
+        self.visit_field_of_view(origin, radius, is_opaque, |index, _|visible.insert(index, ()));
+        visible
+    }
+}
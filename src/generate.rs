@@ -0,0 +1,114 @@
+use std::sync::Arc;
+use crate::{corners, AStarOptions, AStarVisit, Chunk, ChunkIndex, Grid, Ray, RayVisit, CHUNK_SIZE};
+
+/// Produces the content of chunks on first access, see `Grid::get_or_generate`
+///
+/// Generators should be deterministic, e.g. by seeding any randomness using `chunk_seed`,
+/// such that a chunk can be generated again after being removed
+pub trait ChunkGenerator<T, const N: usize = CHUNK_SIZE> {
+    /// Fills the empty `chunk` at the chunk coordinate
+    fn generate(&self, coord:(i32, i32), chunk:&mut Chunk<T, N>);
+}
+
+impl<T, F:Fn((i32, i32), &mut Chunk<T, N>), const N: usize> ChunkGenerator<T, N> for F {
+    fn generate(&self, coord:(i32, i32), chunk:&mut Chunk<T, N>) {
+        self(coord, chunk)
+    }
+}
+
+/// Derives a deterministic seed for the chunk at the chunk coordinate from a world `seed`
+pub fn chunk_seed(seed:u64, coord:(i32, i32)) -> u64 {
+    // splitmix64 finalizer
+    let mut z = seed ^ ((coord.0 as u32 as u64) << 32 | coord.1 as u32 as u64);
+    z = z.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Generates the chunk at the chunk coordinate if it is not present in the grid and has not been generated before
+    ///
+    /// Chunks left empty by the generator are not kept, but remembered such that they are not generated again
+    /// until the chunk is removed using `remove_chunk`. The grid remembers every chunk it generated, which is serialized
+    /// with the grid, so long-lived grids should remove chunks they no longer need or be streamed using `StreamingGrid`.
+    /// Generated chunks are not marked dirty, but are saved by a `StreamingGrid` owning the grid.
+    /// Returns `true` if the generator was invoked
+    pub fn ensure_chunk<G:ChunkGenerator<T, N>>(&mut self, coord:impl Into<(i32, i32)>, generator:&G) -> bool {
+        let coord:(i32, i32) = coord.into();
+        let Some(chunk_index) = ChunkIndex::from_coord::<N>(coord) else {
+            return false;
+        };
+        if self.chunks.contains_key(&chunk_index) || !self.generated.insert(chunk_index) {
+            return false;
+        }
+        let mut chunk = Chunk::new(coord);
+        generator.generate(coord, &mut chunk);
+        if !chunk.is_empty() {
+            self.chunks.insert(chunk_index, Arc::new(chunk));
+        }
+        if let Some(unsaved) = &mut self.dirty.unsaved {
+            unsaved.insert(chunk_index);
+        }
+        true
+    }
+
+    /// Generates all missing chunks overlapping the rectangle spanned by `min` and `max` (both inclusive)
+    pub fn ensure_region<G:ChunkGenerator<T, N>>(&mut self, min:impl Into<(i32, i32)>, max:impl Into<(i32, i32)>, generator:&G) {
+        let (min, max) = corners(min.into(), max.into());
+        let (c0, c1) = (self.chunk_of(min), self.chunk_of(max));
+        for y in c0.1..=c1.1 {
+            for x in c0.0..=c1.0 {
+                self.ensure_chunk((x, y), generator);
+            }
+        }
+    }
+
+    /// Gets a immutable reference to `T`, generating its chunk first if it is not present in the grid
+    pub fn get_or_generate<G:ChunkGenerator<T, N>>(&mut self, index:impl Into<(i32, i32)>, generator:&G) -> Option<&T> {
+        let index:(i32, i32) = index.into();
+        self.ensure_chunk(self.chunk_of(index), generator);
+        self.get(index)
+    }
+
+    /// Gets an mutable reference to `T`, generating its chunk first if it is not present in the grid
+    pub fn get_or_generate_mut<G:ChunkGenerator<T, N>>(&mut self, index:impl Into<(i32, i32)>, generator:&G) -> Option<&mut T> {
+        let index:(i32, i32) = index.into();
+        self.ensure_chunk(self.chunk_of(index), generator);
+        self.get_mut(index)
+    }
+
+    /// Perform the A-star algorithm as `astar_with`, generating chunks as the search expands into them
+    ///
+    /// The search only terminates once `end` is found or all reachable cells are expanded,
+    /// so the cost function should block cells beyond some distance if `end` may be unreachable
+    pub fn astar_generated<G:ChunkGenerator<T, N>, F:Fn(AStarVisit<T>)->Option<u32>>(&mut self, start:impl Into<(i32, i32)>, end:impl Into<(i32, i32)>, options:AStarOptions, generator:&G, cost:F) -> Option<(Vec<(i32, i32)>, u32)> {
+        let start = start.into();
+        let end = end.into();
+        self.ensure_chunk(self.chunk_of(start), generator);
+        pathfinding::directed::astar::astar(&start, |n| {
+            self.ensure_region((n.0.saturating_sub(1), n.1.saturating_sub(1)), (n.0.saturating_add(1), n.1.saturating_add(1)), generator);
+            self.neighbours(*n, options.movement, &cost)
+        }, |n|{
+            options.estimate(*n, end)
+        }, |n|{
+            n == &end
+        })
+    }
+
+    /// Casts a ray as `cast_ray`, generating chunks as the ray enters them
+    pub fn cast_ray_generated<G:ChunkGenerator<T, N>, F:FnMut(RayVisit<T>)->bool>(&mut self, start:impl Into<(f32, f32)>, end:impl Into<(f32, f32)>, generator:&G, mut f:F) {
+        let Some(ray) = Ray::new(start, end) else {
+            return;
+        };
+        for step in ray {
+            self.ensure_chunk(self.chunk_of(step.index), generator);
+            let Some(cell) = self.get(step.index) else {
+                break;
+            };
+            if !f(step.visit(cell)) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::{collections::hash_map, iter::FusedIterator, sync::Arc};
+use crate::{make_mut, unwrap_chunk, Chunk, ChunkIndex, ChunkIntoIter, ChunkIter, ChunkIterMut, Grid, Index, CHUNK_SIZE};
+
+/// Flattens an iterator of chunks into an iterator of cells, tracking how many cells remain
+struct Flat<C, I> {
+    chunks:C,
+    current:Option<I>,
+    remaining:usize,
+}
+
+impl<C, I> Flat<C, I> {
+    fn new(chunks:C, remaining:usize) -> Self {
+        Self { chunks, current:None, remaining }
+    }
+}
+
+impl<C, I> Iterator for Flat<C, I> where C:Iterator, C::Item:IntoIterator<IntoIter = I, Item = I::Item>, I:Iterator {
+    type Item = I::Item;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(cell) = self.current.as_mut().and_then(|x|x.next()) {
+                self.remaining -= 1;
+                return Some(cell);
+            }
+            self.current = Some(self.chunks.next()?.into_iter());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+/// Iterator over the chunks of a `Grid`, created by iterating `&Grid`
+pub struct Chunks<'a, T, const N: usize = CHUNK_SIZE>(pub(crate) hash_map::Values<'a, ChunkIndex, Arc<Chunk<T, N>>>);
+
+/// Mutable iterator over the chunks of a `Grid`, created by iterating `&mut Grid`
+///
+/// Chunks shared with a clone of the grid are copied when they are visited, uniform and palette chunks stay compact
+pub struct ChunksMut<'a, T, const N: usize = CHUNK_SIZE>(pub(crate) hash_map::ValuesMut<'a, ChunkIndex, Arc<Chunk<T, N>>>);
+
+/// Owning iterator over the chunks of a `Grid`, copying chunks shared with a clone of the grid
+struct ChunksInto<T, const N: usize>(hash_map::IntoValues<ChunkIndex, Arc<Chunk<T, N>>>);
+
+/// Iterator over the cells of a `Grid`, created by `Grid::iter`
+pub struct Iter<'a, T, const N: usize = CHUNK_SIZE>(Flat<Chunks<'a, T, N>, ChunkIter<'a, T, N>>);
+
+/// Mutable iterator over the cells of a `Grid`, created by `Grid::iter_mut`
+pub struct IterMut<'a, T, const N: usize = CHUNK_SIZE>(Flat<ChunksMut<'a, T, N>, ChunkIterMut<'a, T, N>>);
+
+/// Owning iterator over the cells of a `Grid`, created by `Grid::into_iter`
+pub struct IntoIter<T, const N: usize = CHUNK_SIZE>(Flat<ChunksInto<T, N>, ChunkIntoIter<T, N>>);
+
+/// Iterator over the cells of a `Grid` in chunk order, created by `Grid::iter_ordered`
+pub struct OrderedIter<'a, T, const N: usize = CHUNK_SIZE>(Flat<std::vec::IntoIter<&'a Chunk<T, N>>, ChunkIter<'a, T, N>>);
+
+/// Mutable iterator over the cells of a `Grid` in chunk order, created by `Grid::iter_ordered_mut`
+pub struct OrderedIterMut<'a, T, const N: usize = CHUNK_SIZE>(Flat<std::vec::IntoIter<&'a mut Chunk<T, N>>, ChunkIterMut<'a, T, N>>);
+
+/// Iterator over the indices of a `Grid`, created by `Grid::keys`
+pub struct Keys<'a, T, const N: usize = CHUNK_SIZE>(Iter<'a, T, N>);
+
+/// Iterator over the cells of a `Grid` without their indices, created by `Grid::values`
+pub struct Values<'a, T, const N: usize = CHUNK_SIZE>(Iter<'a, T, N>);
+
+/// Mutable iterator over the cells of a `Grid` without their indices, created by `Grid::values_mut`
+pub struct ValuesMut<'a, T, const N: usize = CHUNK_SIZE>(IterMut<'a, T, N>);
+
+impl<'a, T, const N: usize> Iterator for Chunks<'a, T, N> {
+    type Item = &'a Chunk<T, N>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Arc::as_ref)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for ChunksMut<'a, T, N> {
+    type Item = &'a mut Chunk<T, N>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(make_mut)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T, const N: usize> Iterator for ChunksInto<T, N> {
+    type Item = Chunk<T, N>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(unwrap_chunk)
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = ((i32, i32), &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for IterMut<'a, T, N> {
+    type Item = ((i32, i32), &'a mut T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = ((i32, i32), T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for OrderedIter<'a, T, N> {
+    type Item = ((i32, i32), &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for OrderedIterMut<'a, T, N> {
+    type Item = ((i32, i32), &'a mut T);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for Keys<'a, T, N> {
+    type Item = (i32, i32);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(index, _)|index)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for Values<'a, T, N> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, cell)|cell)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for ValuesMut<'a, T, N> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(_, cell)|cell)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for Chunks<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for ChunksMut<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for Iter<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for IterMut<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+impl<T, const N: usize> ExactSizeIterator for OrderedIter<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for OrderedIterMut<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for Keys<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for Values<'_, T, N> {}
+impl<T, const N: usize> ExactSizeIterator for ValuesMut<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for Chunks<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for ChunksMut<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for Iter<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for IterMut<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for IntoIter<T, N> {}
+impl<T, const N: usize> FusedIterator for OrderedIter<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for OrderedIterMut<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for Keys<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for Values<'_, T, N> {}
+impl<T, const N: usize> FusedIterator for ValuesMut<'_, T, N> {}
+
+impl<T, const N: usize> IntoIterator for Grid<T, N> {
+    type Item = ((i32, i32), T);
+    type IntoIter = IntoIter<T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let len = self.len();
+        IntoIter(Flat::new(ChunksInto(self.chunks.into_values()), len))
+    }
+}
+
+impl<T, const N: usize> Extend<((i32, i32), T)> for Grid<T, N> {
+    /// Inserts all cells of `iter`
+    /// 
+    /// Chunks stay in the grid while the cells are inserted. The chunk of the previous cell is kept,
+    /// such that the chunk map is only used again when a cell lies in another chunk than the previous cell
+    fn extend<I:IntoIterator<Item = ((i32, i32), T)>>(&mut self, iter:I) {
+        let mut current:Option<(ChunkIndex, &mut Chunk<T, N>)> = None;
+        for (index, t) in iter {
+            let index:Index = index.into();
+            let chunk_index = index.chunk_index::<N>();
+            let chunk = match current.take() {
+                Some((last, chunk)) if last == chunk_index => chunk,
+                _ => {
+                    let chunk = self.chunks.entry(chunk_index).or_insert_with(|| Arc::new(Chunk { index:chunk_index, ..Default::default() }));
+                    self.dirty.insert(chunk_index);
+                    make_mut(chunk)
+                },
+            };
+            chunk.insert(index.local_index::<N>(), t);
+            current = Some((chunk_index, chunk));
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<((i32, i32), T)> for Grid<T, N> {
+    fn from_iter<I:IntoIterator<Item = ((i32, i32), T)>>(iter:I) -> Self {
+        let mut grid = Grid::default();
+        grid.extend(iter);
+        grid
+    }
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Iterates over all cells of the grid in arbitrary order
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter(Flat::new(Chunks(self.chunks.values()), self.len()))
+    }
+
+    /// Iterates mutably over all cells of the grid in arbitrary order
+    /// 
+    /// Every uniform and palette chunk visited is expanded into a value per cell, even if none of its cells is changed,
+    /// as the cells sharing a value cannot be borrowed mutably at once. Use `Grid::compact` to store them compactly again
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, N> {
+        let len = self.len();
+        self.dirty.extend(self.chunks.keys().copied());
+        IterMut(Flat::new(ChunksMut(self.chunks.values_mut()), len))
+    }
+
+    /// Iterates over all chunks of the grid ordered by their `ChunkIndex`, i.e. in row-major order
+    /// 
+    /// Unlike iterating `&Grid` the order is deterministic and does not change between runs
+    pub fn chunks_ordered(&self) -> std::vec::IntoIter<&Chunk<T, N>> {
+        let mut chunks:Vec<_> = self.chunks.values().map(Arc::as_ref).collect();
+        chunks.sort_unstable_by_key(|chunk|chunk.index);
+        chunks.into_iter()
+    }
+
+    /// Iterates mutably over all chunks of the grid ordered by their `ChunkIndex`, i.e. in row-major order
+    /// 
+    /// Uniform and palette chunks stay compact unless their cells are iterated mutably, see `Chunk::expand`
+    pub fn chunks_ordered_mut(&mut self) -> std::vec::IntoIter<&mut Chunk<T, N>> {
+        self.dirty.extend(self.chunks.keys().copied());
+        let mut chunks:Vec<_> = self.chunks.values_mut().map(make_mut).collect();
+        chunks.sort_unstable_by_key(|chunk|chunk.index);
+        chunks.into_iter()
+    }
+
+    /// Iterates over all cells of the grid in a deterministic order
+    /// 
+    /// Chunks are visited as by `chunks_ordered` and cells within each chunk in row-major order
+    pub fn iter_ordered(&self) -> OrderedIter<'_, T, N> {
+        OrderedIter(Flat::new(self.chunks_ordered(), self.len()))
+    }
+
+    /// Iterates mutably over all cells of the grid in a deterministic order, see `iter_ordered`
+    /// 
+    /// Expands every uniform and palette chunk visited, even if none of its cells is changed, see `iter_mut`
+    pub fn iter_ordered_mut(&mut self) -> OrderedIterMut<'_, T, N> {
+        let len = self.len();
+        OrderedIterMut(Flat::new(self.chunks_ordered_mut(), len))
+    }
+
+    /// Iterates over the indices of all cells of the grid in arbitrary order
+    pub fn keys(&self) -> Keys<'_, T, N> {
+        Keys(self.iter())
+    }
+
+    /// Iterates over all cells of the grid in arbitrary order
+    pub fn values(&self) -> Values<'_, T, N> {
+        Values(self.iter())
+    }
+
+    /// Iterates mutably over all cells of the grid in arbitrary order
+    /// 
+    /// Expands every uniform and palette chunk visited, even if none of its cells is changed, see `iter_mut`
+    pub fn values_mut(&mut self) -> ValuesMut<'_, T, N> {
+        ValuesMut(self.iter_mut())
+    }
+}
@@ -1,6 +1,38 @@
-use std::collections::{hash_map::{Values, ValuesMut}, HashMap};
-use glam::Vec2;
-use serde::{Deserialize, Serialize};
+use std::{collections::{HashMap, HashSet}, hash::Hash, sync::{Arc, OnceLock}};
+use serde::{de::Error, ser::SerializeStruct, Deserialize, Deserializer, Serialize, Serializer};
+mod entry;
+pub use entry::*;
+mod iter;
+pub use iter::*;
+mod rect;
+pub use rect::*;
+mod path;
+pub use path::*;
+mod fov;
+mod ray;
+pub use ray::*;
+mod transform;
+pub use transform::*;
+mod fill;
+pub use fill::*;
+mod bounds;
+mod stream;
+pub use stream::*;
+mod generate;
+pub use generate::*;
+#[cfg(feature = "file-store")]
+mod region;
+#[cfg(feature = "file-store")]
+pub use region::*;
+mod dirty;
+pub use dirty::*;
+mod undo;
+pub use undo::*;
+mod palette;
+use palette::*;
+mod compact;
+pub use compact::*;
+/// Default width and height of a `Chunk`
 pub const CHUNK_SIZE: usize = 16;
 
 /// Index used internally to identify an element within a cell
@@ -34,101 +66,497 @@ impl From<Index> for (i32, i32) {
 }
 
 impl Index {
-    pub fn chunk_index(&self) -> ChunkIndex {
+    /// Gets the index of the chunk containing the cell, given chunks of size `N`
+    pub fn chunk_index<const N: usize>(&self) -> ChunkIndex {
         ChunkIndex {
-            x: self.x / CHUNK_SIZE as u32,
-            y: self.y / CHUNK_SIZE as u32,
+            x: self.x / N as u32,
+            y: self.y / N as u32,
         }
     }
     
-    pub fn local_index(&self) -> usize {
-        let x = self.x as usize % CHUNK_SIZE;
-        let y = self.y as usize % CHUNK_SIZE;
-        y * CHUNK_SIZE + x
+    /// Gets the position of the cell within its chunk, given chunks of size `N`
+    pub fn local_index<const N: usize>(&self) -> usize {
+        let x = self.x as usize % N;
+        let y = self.y as usize % N;
+        y * N + x
+    }
+}
+
+/// Chunks are ordered in row-major order, i.e. by `y` and then by `x`
+impl Ord for ChunkIndex {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for ChunkIndex {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl ChunkIndex {
-    pub fn index(&self) -> Index {
+    /// Gets the index of the top left cell of the chunk, given chunks of size `N`
+    pub fn index<const N: usize>(&self) -> Index {
         Index {
-            x: self.x * CHUNK_SIZE as u32,
-            y: self.y * CHUNK_SIZE as u32
+            x: self.x * N as u32,
+            y: self.y * N as u32
         }
     }
+
+    /// Gets the signed coordinate of the chunk, given chunks of size `N`
+    /// 
+    /// Chunk `(0, 0)` contains cell `(0, 0)`, chunk `(-1, 0)` contains cell `(-1, 0)` and so on
+    pub fn coord<const N: usize>(&self) -> (i32, i32) {
+        let (x, y):(i32, i32) = self.index::<N>().into();
+        (x.div_euclid(N as i32), y.div_euclid(N as i32))
+    }
+
+    /// Gets the chunk index from a signed chunk coordinate, given chunks of size `N`
+    /// 
+    /// Returns `None` if the coordinate lies outside of the range covered by `i32` cell indices
+    pub fn from_coord<const N: usize>(coord:impl Into<(i32, i32)>) -> Option<Self> {
+        let coord:(i32, i32) = coord.into();
+        let offset = (1i64 << 31) / N as i64;
+        let x = u32::try_from(coord.0 as i64 + offset).ok()?;
+        let y = u32::try_from(coord.1 as i64 + offset).ok()?;
+        let max = ((1u64 << 32) / N as u64) as u32;
+        (x < max && y < max).then_some(Self { x, y })
+    }
 }
 
-/// A `Chunk` of the `Grid`
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Chunk<T> {
+/// Bounding box of the occupied cells of a `Chunk` in local coordinates
+#[derive(Clone, Copy)]
+struct LocalBounds {
+    min:(usize, usize),
+    max:(usize, usize),
+}
+
+impl LocalBounds {
+    fn from_local<const N: usize>(local:usize) -> Self {
+        let p = (local % N, local / N);
+        Self { min:p, max:p }
+    }
+
+    fn expand<const N: usize>(&mut self, local:usize) {
+        let (x, y) = (local % N, local / N);
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn is_on_edge<const N: usize>(&self, local:usize) -> bool {
+        let (x, y) = (local % N, local / N);
+        x == self.min.0 || x == self.max.0 || y == self.min.1 || y == self.max.1
+    }
+}
+
+/// A `Chunk` of the `Grid` holding `N` x `N` cells
+/// 
+/// `N` must be a power of two.
+/// Uniform and palette chunks, see `Grid::fill_chunk` and `Grid::compact_chunk`, share values between cells
+/// and copy a value when a cell sharing it is mutated, using the clone function they were created with.
+/// Chunks are serialized with a value per cell and deserialized as flat chunks, see `Compact` to keep them compact
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "ChunkData<T>")]
+pub struct Chunk<T, const N: usize = CHUNK_SIZE> {
     index:ChunkIndex,
-    len:u16,
-    inner:Vec<Option<T>>
+    len:usize,
+    inner:Vec<Option<T>>,
+    /// How the cells are stored, `inner` is only used by flat chunks
+    storage:Storage<T>,
+    /// Cached bounds of the occupied cells, unset if the chunk is empty or the bounds must be recomputed on the next access
+    bounds:OnceLock<LocalBounds>,
+    /// Clones the cells once the chunk is shared between clones of a grid, set by `Grid::clone` before sharing the chunk
+    shared_clone:OnceLock<CloneCell<T>>,
+}
+
+impl<T:Serialize, const N: usize> Serialize for Chunk<T, N> {
+    fn serialize<S:Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Chunk", 3)?;
+        state.serialize_field("index", &self.index)?;
+        state.serialize_field("len", &self.len)?;
+        state.serialize_field("inner", &FlatCells(self))?;
+        state.end()
+    }
+}
+
+/// Serializes the cells of a chunk as a value per cell, borrowing the shared values of uniform and palette chunks
+struct FlatCells<'a, T, const N: usize>(&'a Chunk<T, N>);
+
+impl<T:Serialize, const N: usize> Serialize for FlatCells<'_, T, N> {
+    fn serialize<S:Serializer>(&self, serializer:S) -> Result<S::Ok, S::Error> {
+        match &self.0.storage {
+            Storage::Flat => self.0.inner.serialize(serializer),
+            _ => serializer.collect_seq((0..N * N).map(|local|self.0.get_local(local).expect("local index within the chunk"))),
+        }
+    }
+}
+
+/// How the cells of a `Chunk` are stored
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Clone"))]
+enum Storage<T> {
+    /// A value per cell in `inner`, which is empty if the chunk is empty
+    #[default]
+    Flat,
+
+    /// Every cell holds the value, which is always `Some` such that cells can be borrowed as `&Option<T>`
+    Uniform(Option<T>, #[serde(skip, default = "CellFns::cloning")] CellFns<T>),
+
+    /// Distinct values with a bit-packed index per cell, upgraded to `Flat` once it holds too many distinct values
+    Palette(Palette<T>),
+}
+
+/// A deserialized flat `Chunk` which is validated before it is used, see `Chunk::try_from`
+#[derive(Deserialize)]
+struct ChunkData<T> {
+    index:ChunkIndex,
+    len:usize,
+    inner:Vec<Option<T>>,
+}
+
+impl<T, const N: usize> TryFrom<ChunkData<T>> for Chunk<T, N> {
+    type Error = String;
+
+    fn try_from(data:ChunkData<T>) -> Result<Self, Self::Error> {
+        Self::from_parts(data.index, data.len, data.inner, Storage::Flat)
+    }
+}
+
+impl<T, const N: usize> Chunk<T, N> {
+    /// Rejects chunks whose index lies outside of the grid or whose length does not match their cells, which would otherwise panic once accessed
+    fn from_parts(index:ChunkIndex, len:usize, inner:Vec<Option<T>>, storage:Storage<T>) -> Result<Self, String> {
+        let max = (1u64 << 32) / N as u64;
+        if index.x as u64 >= max || index.y as u64 >= max {
+            return Err(format!("invalid chunk index {:?} for chunks of size {}", index, N));
+        }
+        let valid = match &storage {
+            Storage::Flat if inner.is_empty() => len == 0,
+            Storage::Flat => inner.len() == N * N && inner.iter().filter(|x|x.is_some()).count() == len,
+            Storage::Uniform(value, _) => inner.is_empty() && value.is_some() && len == N * N,
+            Storage::Palette(palette) => inner.is_empty() && len > 0 && palette.is_valid::<N>(len),
+        };
+        if !valid {
+            return Err(format!("invalid cells of chunk at {:?}", index.coord::<N>()));
+        }
+        Ok(Self { index, len, inner, storage, bounds:OnceLock::new(), shared_clone:OnceLock::new() })
+    }
+}
+
+/// Clones a cell, stored by chunks which need to copy cells without requiring `T: Clone` themselves
+pub(crate) type CloneCell<T> = fn(&T) -> T;
+
+/// Compares two cells, stored by palette chunks created from `T: Eq` values
+pub(crate) type EqCell<T> = fn(&T, &T) -> bool;
+
+/// Functions on cells stored by uniform and palette chunks, which are only created by methods requiring `T: Clone`
+pub(crate) struct CellFns<T> {
+    pub(crate) clone:CloneCell<T>,
+    /// Finds existing palette entries, without it new values get their own entry
+    pub(crate) eq:Option<EqCell<T>>,
+}
+
+impl<T> Clone for CellFns<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for CellFns<T> {}
+
+impl<T:Clone> CellFns<T> {
+    /// Gets the functions of chunks created from `T: Clone` values
+    pub(crate) fn cloning() -> Self {
+        Self { clone:T::clone, eq:None }
+    }
 }
 
-impl<T:Clone> Default for Chunk<T> {
+impl<T:Eq + Clone> CellFns<T> {
+    /// Gets the functions of chunks created from `T: Eq` values, which reuse palette entries of equal values
+    pub(crate) fn comparing() -> Self {
+        Self { clone:T::clone, eq:Some(T::eq) }
+    }
+}
+
+impl<T, const N: usize> Chunk<T, N> {
+    /// Fails compilation if `N` is not a valid chunk size
+    const VALID_SIZE: () = assert!(N.is_power_of_two() && N <= 1 << 16, "chunk size must be a power of two no larger than 65536");
+}
+
+impl<T, const N: usize> Default for Chunk<T, N> {
     fn default() -> Self {
-        Self { index:Index::from((0, 0)).chunk_index(), len:0, inner: Vec::new() }
+        let () = Self::VALID_SIZE;
+        Self { index:Index::from((0, 0)).chunk_index::<N>(), len:0, inner: Vec::new(), storage:Storage::Flat, bounds:OnceLock::new(), shared_clone:OnceLock::new() }
     }
 }
 
-impl<T:Clone> Chunk<T> {
+impl<T, const N: usize> Chunk<T, N> {
+    /// Creates an empty chunk at the chunk coordinate, see `ChunkIndex::coord`
+    /// 
+    /// Panics if the coordinate lies outside of the range covered by `i32` cell indices
+    pub fn new(coord:impl Into<(i32, i32)>) -> Self {
+        let index = ChunkIndex::from_coord::<N>(coord).expect("chunk coordinate out of range");
+        Self { index, ..Default::default() }
+    }
+
+    /// Gets the signed coordinate of the chunk, see `ChunkIndex::coord`
+    pub fn coord(&self) -> (i32, i32) {
+        self.index.coord::<N>()
+    }
+
     /// Gets the top left index of the chunk
     pub fn top_left(&self) -> (i32, i32) {
-        self.index.index().into()
+        self.index.index::<N>().into()
     }
 
     /// Gets the bottom right index of the chunk
     pub fn bottom_right(&self) -> (i32, i32) {
-        let p:(i32, i32) = self.index.index().into();
-        (p.0 + CHUNK_SIZE as i32 - 1, p.1 + CHUNK_SIZE as i32 - 1)
+        let p:(i32, i32) = self.index.index::<N>().into();
+        (p.0 + N as i32 - 1, p.1 + N as i32 - 1)
     }
 
     /// Get length of the chunk, i.e. how many elements are in the chunk.
     pub fn len(&self) -> usize {
-        self.len as usize
+        self.len
+    }
+
+    /// Returns `true` if the chunk contains no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     /// Clear all elements from the chunk
     pub fn clear(&mut self) {
         self.len = 0;
         self.inner = Vec::default();
+        self.storage = Storage::Flat;
+        self.bounds = OnceLock::new();
+    }
+
+    /// Returns `true` if all cells of the chunk hold the same value without storing it per cell, see `Grid::fill_chunk`
+    pub fn is_uniform(&self) -> bool {
+        matches!(self.storage, Storage::Uniform(..))
+    }
+
+    /// Returns `true` if the chunk stores a palette of its values and an index per cell, see `Grid::compact_chunk`
+    pub fn is_palette(&self) -> bool {
+        matches!(self.storage, Storage::Palette(_))
+    }
+
+    /// Gets the number of palette entries, including the empty cell and entries no longer used by any cell,
+    /// or `None` if the chunk is not a palette chunk
+    pub fn palette_len(&self) -> Option<usize> {
+        match &self.storage {
+            Storage::Palette(palette) => Some(palette.len()),
+            _ => None,
+        }
+    }
+
+    /// Copies the chunk using `clone` for every value stored per cell, uniform and palette chunks use their own clone function
+    pub(crate) fn clone_with(&self, clone:CloneCell<T>) -> Self {
+        let storage = match &self.storage {
+            Storage::Flat => Storage::Flat,
+            Storage::Uniform(value, fns) => Storage::Uniform(value.as_ref().map(fns.clone), *fns),
+            Storage::Palette(palette) => Storage::Palette(palette.duplicate()),
+        };
+        let inner = self.inner.iter().map(|x|x.as_ref().map(clone)).collect();
+        Self { index:self.index, len:self.len, inner, storage, bounds:self.bounds.clone(), shared_clone:self.shared_clone.clone() }
+    }
+
+    /// Expands a uniform or palette chunk into a value per cell
+    pub fn expand(&mut self) {
+        self.inner = match std::mem::take(&mut self.storage) {
+            Storage::Flat => return,
+            Storage::Uniform(value, fns) => {
+                let clone = fns.clone;
+                let mut inner = Vec::with_capacity(N * N);
+                inner.extend(std::iter::repeat_with(|| value.as_ref().map(clone)).take(N * N - 1));
+                inner.push(value);
+                inner
+            },
+            Storage::Palette(palette) => palette.expand::<N>(),
+        };
+    }
+
+    /// Turns a uniform chunk into a palette chunk, which can change single cells
+    fn split_uniform(&mut self) {
+        if let Storage::Uniform(value, fns) = &mut self.storage {
+            self.storage = Storage::Palette(Palette::filled::<N>(value.take(), *fns));
+        }
+    }
+
+    /// Creates a uniform chunk at the chunk coordinate with every cell holding `value`, copied and compared using `fns`
+    pub(crate) fn filled(coord:(i32, i32), value:T, fns:CellFns<T>) -> Self {
+        let mut chunk = Self::new(coord);
+        chunk.len = N * N;
+        chunk.storage = Storage::Uniform(Some(value), fns);
+        chunk
+    }
+
+    /// Gets the top left and bottom right index of the occupied cells of the chunk, or `None` if the chunk is empty
+    /// 
+    /// The bounds are cached and only recomputed on the next access after a cell on the edge of the bounds has been removed
+    pub fn bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+        if self.len == 0 {
+            return None;
+        }
+        let bounds = match self.bounds.get() {
+            Some(bounds) => *bounds,
+            None => {
+                let bounds = self.compute_bounds()?;
+                *self.bounds.get_or_init(|| bounds)
+            },
+        };
+        let p = self.top_left();
+        Some((
+            (p.0 + bounds.min.0 as i32, p.1 + bounds.min.1 as i32),
+            (p.0 + bounds.max.0 as i32, p.1 + bounds.max.1 as i32),
+        ))
+    }
+
+    fn compute_bounds(&self) -> Option<LocalBounds> {
+        if self.is_uniform() {
+            let mut bounds = LocalBounds::from_local::<N>(0);
+            bounds.expand::<N>(N * N - 1);
+            return Some(bounds);
+        }
+        let mut occupied = (0..N * N).filter(|local|self.get_local(*local).is_some_and(|x|x.is_some()));
+        let mut bounds = LocalBounds::from_local::<N>(occupied.next()?);
+        occupied.for_each(|local|bounds.expand::<N>(local));
+        Some(bounds)
     }
 
     /// Get element in chunk using local position within the chunk
     pub fn get_local(&self, local:usize) -> Option<&Option<T>> {
-        self.inner.get(local)
+        match &self.storage {
+            Storage::Flat => self.inner.get(local),
+            Storage::Uniform(value, _) => (local < N * N).then_some(value),
+            Storage::Palette(palette) => (local < N * N).then(|| palette.get(local)),
+        }
     }
 
     /// Insert element into local position
+    /// 
+    /// A palette chunk is upgraded to a value per cell once it holds too many distinct values
     pub fn insert(&mut self, local:usize, t:T) {
-        if self.inner.is_empty() {
-            self.inner = vec![None; CHUNK_SIZE * CHUNK_SIZE];
-            self.len = 0;
+        assert!(local < N * N, "local index out of range");
+        let vacant = self.get_local(local).is_none_or(|x|x.is_none());
+        self.split_uniform();
+        let rest = match &mut self.storage {
+            Storage::Palette(palette) => palette.insert::<N>(local, t).err(),
+            _ => Some(t),
+        };
+        if let Some(t) = rest {
+            self.expand();
+            if self.inner.is_empty() {
+                self.inner = std::iter::repeat_with(|| None).take(N * N).collect();
+            }
+            self.inner[local] = Some(t);
         }
-        if self.inner[local].is_none() {
+        if vacant {
+            if self.len == 0 {
+                self.bounds = OnceLock::from(LocalBounds::from_local::<N>(local));
+            } else if let Some(bounds) = self.bounds.get_mut() {
+                bounds.expand::<N>(local);
+            }
             self.len += 1;
         }
-        self.inner[local] = Some(t);
     }
 
     /// Get element in chunk using local position within the `chunk`
+    /// 
+    /// A cell sharing its value with other cells of a uniform or palette chunk gets its own copy of the value
     pub fn get_local_mut(&mut self, local:usize) -> Option<&mut T> {
+        if local >= N * N {
+            return None;
+        }
+        self.split_uniform();
+        if let Storage::Palette(palette) = &mut self.storage {
+            if !palette.unshare::<N>(local) {
+                self.expand();
+            }
+        }
+        if let Storage::Palette(palette) = &mut self.storage {
+            return palette.get_mut(local);
+        }
         let m = self.inner.get_mut(local)?;
         m.as_mut()
     }
+
+    /// Remove element from local position, returning it if it was present
+    /// 
+    /// The backing storage is freed when the last element is removed.
+    /// A value shared with other cells of a uniform or palette chunk is cloned
+    pub fn remove(&mut self, local:usize) -> Option<T> {
+        if local >= N * N {
+            return None;
+        }
+        self.split_uniform();
+        let t = match &mut self.storage {
+            Storage::Palette(palette) => palette.remove(local)?,
+            _ => self.inner.get_mut(local)?.take()?,
+        };
+        self.len -= 1;
+        if self.bounds.get().is_some_and(|x|x.is_on_edge::<N>(local)) {
+            self.bounds.take();
+        }
+        if self.len == 0 {
+            self.clear();
+        }
+        Some(t)
+    }
 }
 
-pub struct ChunkIter<'a, T> {
+impl<T:Eq + Hash + Clone, const N: usize> Chunk<T, N> {
+    /// Stores the cells of a flat chunk using a palette, or as a uniform chunk if every cell holds the same value,
+    /// returning `false` if the chunk is empty or holds too many distinct values
+    pub(crate) fn compact(&mut self) -> bool {
+        if !matches!(self.storage, Storage::Flat) {
+            return true;
+        }
+        if self.is_empty() {
+            return false;
+        }
+        let Some(palette) = Palette::from_cells::<N>(&self.inner) else {
+            return false;
+        };
+        self.storage = match self.inner.first() {
+            Some(Some(first)) if self.len == N * N && palette.len() == 2 => Storage::Uniform(Some(first.clone()), CellFns::comparing()),
+            _ => Storage::Palette(palette),
+        };
+        self.inner = Vec::new();
+        true
+    }
+}
+
+pub struct ChunkIter<'a, T, const N: usize = CHUNK_SIZE> {
     index:usize,
     top_left:(i32, i32),
     iter:core::slice::Iter<'a, Option<T>>,
+    /// Storage of the chunk, cells of uniform and palette chunks are looked up by index in which case `iter` is empty
+    storage:&'a Storage<T>,
 }
-impl<'a, T> Iterator for ChunkIter<'a, T> {
+impl<'a, T, const N: usize> Iterator for ChunkIter<'a, T, N> {
     type Item = ((i32, i32), &'a T);
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(next) = self.iter.next() {
+        let cell = |local:usize| match self.storage {
+            Storage::Flat => &None,
+            Storage::Uniform(value, _) => value,
+            Storage::Palette(palette) => palette.get(local),
+        };
+        if !matches!(self.storage, Storage::Flat) {
+            while self.index < N * N {
+                let local = self.index;
+                self.index += 1;
+                if let Some(cell) = cell(local) {
+                    return Some(((self.top_left.0 + local as i32 % N as i32, self.top_left.1 + local as i32 / N as i32), cell));
+                }
+            }
+            return None;
+        }
+        for next in self.iter.by_ref() {
             if let Some(cell) = next {
-                let index = (self.top_left.0 + self.index as i32 % CHUNK_SIZE as i32, self.top_left.1 + self.index as i32 / CHUNK_SIZE as i32);
+                let index = (self.top_left.0 + self.index as i32 % N as i32, self.top_left.1 + self.index as i32 / N as i32);
                 self.index += 1;
                 return Some((index, cell));
             }
@@ -140,17 +568,17 @@ impl<'a, T> Iterator for ChunkIter<'a, T> {
     }
 }
 
-pub struct ChunkIterMut<'a, T> {
+pub struct ChunkIterMut<'a, T, const N: usize = CHUNK_SIZE> {
     index:usize,
     top_left:(i32, i32),
     iter:core::slice::IterMut<'a, Option<T>>,
 }
-impl<'a, T> Iterator for ChunkIterMut<'a, T> {
+impl<'a, T, const N: usize> Iterator for ChunkIterMut<'a, T, N> {
     type Item = ((i32, i32), &'a mut T);
     fn next(&mut self) -> Option<Self::Item> {
-        while let Some(next) = self.iter.next() {
+        for next in self.iter.by_ref() {
             if let Some(cell) = next {
-                let index = (self.top_left.0 + self.index as i32 % CHUNK_SIZE as i32, self.top_left.1 + self.index as i32 / CHUNK_SIZE as i32);
+                let index = (self.top_left.0 + self.index as i32 % N as i32, self.top_left.1 + self.index as i32 / N as i32);
                 self.index += 1;
                 return Some((index, cell));
             }
@@ -162,73 +590,234 @@ impl<'a, T> Iterator for ChunkIterMut<'a, T> {
     }
 }
 
-impl<'a, T:Clone> IntoIterator for &'a Chunk<T> {
+pub struct ChunkIntoIter<T, const N: usize = CHUNK_SIZE> {
+    index:usize,
+    top_left:(i32, i32),
+    iter:std::vec::IntoIter<Option<T>>,
+}
+impl<T, const N: usize> Iterator for ChunkIntoIter<T, N> {
+    type Item = ((i32, i32), T);
+    fn next(&mut self) -> Option<Self::Item> {
+        for next in self.iter.by_ref() {
+            if let Some(cell) = next {
+                let index = (self.top_left.0 + self.index as i32 % N as i32, self.top_left.1 + self.index as i32 / N as i32);
+                self.index += 1;
+                return Some((index, cell));
+            }
+            self.index += 1;
+            continue;
+        }
+
+        None
+    }
+}
+
+/// Uniform and palette chunks are expanded first, see `Chunk::expand`
+impl<T, const N: usize> IntoIterator for Chunk<T, N> {
+    type Item = ((i32, i32), T);
+    type IntoIter = ChunkIntoIter<T, N>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.expand();
+        let top_left = self.top_left();
+        Self::IntoIter {
+            index:0,
+            iter: self.inner.into_iter(),
+            top_left
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a Chunk<T, N> {
     type Item = ((i32, i32), &'a T);
-    type IntoIter = ChunkIter<'a, T>;
+    type IntoIter = ChunkIter<'a, T, N>;
 
     fn into_iter(self) -> Self::IntoIter {
         Self::IntoIter {
             index:0,
             iter: self.inner.iter(),
+            storage: &self.storage,
             top_left: self.top_left()
         }
     }
 }
 
-impl<'a, T:Clone> IntoIterator for &'a mut Chunk<T> {
+/// Uniform and palette chunks are expanded first, even if none of their cells is changed, see `Chunk::expand`
+impl<'a, T, const N: usize> IntoIterator for &'a mut Chunk<T, N> {
     type Item = ((i32, i32), &'a mut T);
-    type IntoIter = ChunkIterMut<'a, T>;
+    type IntoIter = ChunkIterMut<'a, T, N>;
 
     fn into_iter(self) -> Self::IntoIter {
+        self.expand();
         let top_left = self.top_left();
         Self::IntoIter {
             index:0,
             iter: self.inner.iter_mut(),
-            top_left: top_left
+            top_left
         }
     }
 }
 
 /// An endless 2D grid of type `T` implemented using chunks
-#[derive(Default, Clone, Serialize, Deserialize)]
-pub struct Grid<T> {
-    chunks: HashMap<ChunkIndex, Chunk<T>>,
+/// 
+/// Chunks are shared between clones of a grid and only copied on the first mutable access, see `Grid::snapshot`.
+/// As the chunks are reference counted using `Arc`, the grid is only `Send` and `Sync` if `T` is both `Send` and `Sync`,
+/// e.g. a `Grid<Cell<u8>>` cannot be sent to another thread.
+/// Chunks are serialized in `ChunkIndex` order such that the serialized output is reproducible.
+/// Uniform and palette chunks are serialized with a value per cell, see `Compact` to keep them compact
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
+pub struct Grid<T, const N: usize = CHUNK_SIZE> {
+    #[serde(serialize_with = "serialize_chunks", deserialize_with = "deserialize_chunks")]
+    chunks: HashMap<ChunkIndex, Arc<Chunk<T, N>>>,
+    /// Chunks modified since the dirty flags were last cleared, see `Grid::dirty_chunks`
+    #[serde(skip)]
+    dirty: DirtyChunks,
+    /// Chunks which have been generated, including chunks left empty by the generator, see `Grid::ensure_chunk`
+    /// 
+    /// Grows by one entry per generated chunk until the chunk is removed, and is copied along with the grid by `Grid::snapshot`
+    #[serde(serialize_with = "serialize_generated", default)]
+    generated: HashSet<ChunkIndex>,
 }
 
-impl<'a, T> IntoIterator for &'a Grid<T> {
-    type Item = &'a Chunk<T>;
+/// Gets the function copying a chunk shared with a clone of the grid, which `Grid::clone` sets before sharing the chunk
+fn shared_clone<T, const N: usize>(chunk:&Chunk<T, N>) -> CloneCell<T> {
+    *chunk.shared_clone.get().expect("chunks are only shared by `Grid::clone`")
+}
 
-    type IntoIter = Values<'a, ChunkIndex, Chunk<T>>;
+/// Gets mutable access to a chunk, copying it first if it is shared with a clone of the grid
+pub(crate) fn make_mut<T, const N: usize>(chunk:&mut Arc<Chunk<T, N>>) -> &mut Chunk<T, N> {
+    if Arc::get_mut(chunk).is_none() {
+        *chunk = Arc::new(chunk.clone_with(shared_clone(chunk)));
+    }
+    Arc::get_mut(chunk).expect("chunk should not be shared")
+}
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.chunks.values()
+/// Gets mutable access to a chunk as by `make_mut`, expanding uniform and palette chunks
+pub(crate) fn make_flat<T, const N: usize>(chunk:&mut Arc<Chunk<T, N>>) -> &mut Chunk<T, N> {
+    let chunk = make_mut(chunk);
+    chunk.expand();
+    chunk
+}
+
+/// Takes a chunk out of its `Arc`, copying it if it is shared with a clone of the grid
+pub(crate) fn unwrap_chunk<T, const N: usize>(chunk:Arc<Chunk<T, N>>) -> Chunk<T, N> {
+    Arc::try_unwrap(chunk).unwrap_or_else(|chunk|chunk.clone_with(shared_clone(&chunk)))
+}
+
+/// Cloning only clones the `Arc` of each chunk, chunks are copied on the first mutable access by either grid
+impl<T:Clone, const N: usize> Clone for Grid<T, N> {
+    fn clone(&self) -> Self {
+        for chunk in self.chunks.values() {
+            chunk.shared_clone.get_or_init(|| T::clone);
+        }
+        Self { chunks:self.chunks.clone(), dirty:self.dirty.clone(), generated:self.generated.clone() }
     }
 }
 
+impl<T:Clone, const N: usize> Grid<T, N> {
+    /// Creates a copy of the grid in O(number of chunks), same as `clone`
+    /// 
+    /// The chunks are shared with the snapshot until either grid mutates them, at which point only the mutated chunk is copied
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    /// Fills every cell of the chunk at the chunk coordinate with `value`, returning the chunk it replaced
+    /// 
+    /// The chunk stores `value` only once and turns into a palette chunk once a cell is changed, see `Chunk::is_uniform`.
+    /// Every value then written gets its own palette entry, see `fill_chunk_eq` to share entries of equal values.
+    /// Does nothing and returns `None` if the coordinate lies outside of the range covered by `i32` cell indices
+    pub fn fill_chunk(&mut self, coord:impl Into<(i32, i32)>, value:T) -> Option<Chunk<T, N>> {
+        let coord = coord.into();
+        ChunkIndex::from_coord::<N>(coord)?;
+        self.insert_chunk(Chunk::filled(coord, value, CellFns::cloning()))
+    }
+}
 
-impl<'a, T> IntoIterator for &'a mut Grid<T> {
-    type Item = &'a mut Chunk<T>;
+impl<T:Eq + Clone, const N: usize> Grid<T, N> {
+    /// Fills the chunk at the chunk coordinate as by `fill_chunk`, returning the chunk it replaced
+    /// 
+    /// Once the chunk is split into a palette chunk, cells set to a value already in the palette share its entry,
+    /// so the chunk stays compact as long as it holds at most 255 distinct values
+    pub fn fill_chunk_eq(&mut self, coord:impl Into<(i32, i32)>, value:T) -> Option<Chunk<T, N>> {
+        let coord = coord.into();
+        ChunkIndex::from_coord::<N>(coord)?;
+        self.insert_chunk(Chunk::filled(coord, value, CellFns::comparing()))
+    }
+}
 
-    type IntoIter = ValuesMut<'a, ChunkIndex, Chunk<T>>;
+impl<T:Eq + Hash + Clone, const N: usize> Grid<T, N> {
+    /// Stores the chunk at the chunk coordinate as a palette of its distinct values and a bit-packed index per cell,
+    /// or as a uniform chunk if every cell holds the same value, returning `true` if the chunk is stored compactly
+    /// 
+    /// Chunks with at most 255 distinct values take a fraction of the memory and are accessed the same way.
+    /// They are upgraded back to a value per cell once they hold more distinct values
+    pub fn compact_chunk(&mut self, coord:impl Into<(i32, i32)>) -> bool {
+        let Some(chunk_index) = ChunkIndex::from_coord::<N>(coord) else {
+            return false;
+        };
+        self.chunks.get_mut(&chunk_index).is_some_and(|chunk|chunk.is_palette() || chunk.is_uniform() || make_mut(chunk).compact())
+    }
+
+    /// Compacts all chunks as by `compact_chunk`, returning the number of chunks stored compactly
+    pub fn compact(&mut self) -> usize {
+        // chunks which are already compact are not copied if shared
+        self.chunks.values_mut().map(|chunk|chunk.is_palette() || chunk.is_uniform() || make_mut(chunk).compact()).filter(|x|*x).count()
+    }
+}
+
+fn serialize_chunks<T: Serialize, S: Serializer, const N: usize>(chunks: &HashMap<ChunkIndex, Arc<Chunk<T, N>>>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut chunks:Vec<_> = chunks.iter().collect();
+    chunks.sort_unstable_by_key(|(chunk_index, _)|**chunk_index);
+    serializer.collect_map(chunks)
+}
+
+fn serialize_generated<S: Serializer>(generated: &HashSet<ChunkIndex>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut generated:Vec<_> = generated.iter().collect();
+    generated.sort_unstable();
+    serializer.collect_seq(generated)
+}
+
+fn deserialize_chunks<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(deserializer: D) -> Result<HashMap<ChunkIndex, Arc<Chunk<T, N>>>, D::Error> {
+    chunks_at_index(HashMap::<ChunkIndex, Chunk<T, N>>::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Rejects chunks stored under another `ChunkIndex` than their own, which would otherwise not be found by their cells
+fn chunks_at_index<T, const N: usize>(chunks: HashMap<ChunkIndex, Chunk<T, N>>) -> Result<HashMap<ChunkIndex, Arc<Chunk<T, N>>>, String> {
+    chunks.into_iter().map(|(chunk_index, chunk)|if chunk_index == chunk.index {
+        Ok((chunk_index, Arc::new(chunk)))
+    } else {
+        Err(format!("chunk {:?} is stored at {:?}", chunk.index, chunk_index))
+    }).collect()
+}
+
+impl<T, const N: usize> Default for Grid<T, N> {
+    fn default() -> Self {
+        Self { chunks:HashMap::default(), dirty:DirtyChunks::default(), generated:HashSet::default() }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a Grid<T, N> {
+    type Item = &'a Chunk<T, N>;
+
+    type IntoIter = Chunks<'a, T, N>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.chunks.values_mut()
+        Chunks(self.chunks.values())
     }
 }
 
-/// Struct used by the `Grid::cast_ray`
-pub struct RayVisit<'a, T> {
-    /// Current index of the cell being visited
-    pub index:(i32, i32),
 
-    /// The cell being visited
-    pub cell:&'a T,
+impl<'a, T, const N: usize> IntoIterator for &'a mut Grid<T, N> {
+    type Item = &'a mut Chunk<T, N>;
 
-    /// Current position of the ray
-    pub pos:(f32, f32),
-   
-    // Distance traveled by the ray
-    pub d:f32
+    type IntoIter = ChunksMut<'a, T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.dirty.extend(self.chunks.keys().copied());
+        ChunksMut(self.chunks.values_mut())
+    }
 }
 
 /// Struct used by the `Grid::astar`
@@ -238,22 +827,38 @@ pub struct AStarVisit<'a, T> {
 
     /// The cell being visited
     pub cell:&'a T,
+
+    /// Index of the cell from which the visited cell is entered
+    pub from:(i32, i32),
+}
+
+impl<T> AStarVisit<'_, T> {
+    /// Returns `true` if the visited cell is entered diagonally
+    pub fn is_diagonal(&self) -> bool {
+        self.index.0 != self.from.0 && self.index.1 != self.from.1
+    }
 }
 
-impl<T: Clone> Grid<T> {
+impl<T, const N: usize> Grid<T, N> {
     /// Gets length of the grid, aka. how many cells there are
     pub fn len(&self) -> usize {
         let mut len = 0;
         self.chunks.values().for_each(|x|len += x.len());
         len
     }
+
+    /// Returns `true` if the grid contains no cells
+    pub fn is_empty(&self) -> bool {
+        self.chunks.values().all(|x|x.is_empty())
+    }
+
     /// Gets a immutable reference to `T`
     pub fn get(&self, index: impl Into<(i32, i32)>) -> Option<&T> {
         let index:(i32, i32) = index.into();
         let index = Index::from(index);
-        let chunk_index = index.chunk_index();
+        let chunk_index = index.chunk_index::<N>();
         let chunk = self.chunks.get(&chunk_index)?;
-        let cell = chunk.get_local(index.local_index())?;
+        let cell = chunk.get_local(index.local_index::<N>())?;
         let cell = cell.as_ref()?;
         Some(cell)
     }
@@ -262,124 +867,103 @@ impl<T: Clone> Grid<T> {
     pub fn get_mut(&mut self, index: impl Into<(i32, i32)>) -> Option<&mut T> {
         let index:(i32, i32) = index.into();
         let index:Index = index.into();
-        let chunk_index = index.chunk_index();
+        let chunk_index = index.chunk_index::<N>();
+        let local = index.local_index::<N>();
         let chunk = self.chunks.get_mut(&chunk_index)?;
-        chunk.get_local_mut(index.local_index())
+        chunk.get_local(local)?.as_ref()?;
+        self.dirty.insert(chunk_index);
+        make_mut(chunk).get_local_mut(local)
     }
 
     /// Insert `T`
     pub fn insert(&mut self, index: impl Into<(i32, i32)>, t: T) {
         let index:(i32, i32) = index.into();
         let index:Index = index.into();
-        let chunk_index = index.chunk_index();
-        let chunk = match self.chunks.get_mut(&chunk_index) {
-            Some(chunk) => chunk,
-            None => {
-                let mut chunk = Chunk::default();
-                chunk.index = chunk_index;
-                self.chunks.insert(chunk_index, chunk);
-                self.chunks.get_mut(&chunk_index).unwrap()
-            }
-        };
-        let local = index.local_index();
+        let chunk_index = index.chunk_index::<N>();
+        let chunk = self.chunks.entry(chunk_index).or_insert_with(|| Arc::new(Chunk { index:chunk_index, ..Default::default() }));
+        let chunk = make_mut(chunk);
+        let local = index.local_index::<N>();
         chunk.insert(local, t);
+        self.dirty.insert(chunk_index);
     }
 
-    /// Perform the A-star algorithm
-    /// `F is a function which returns `false` when path is blocked and `true` when not blocked
-    pub fn astar<F:Fn(AStarVisit<T>)->bool>(&self, start:impl Into<(i32, i32)>, end:impl Into<(i32, i32)>, visit:F) -> Option<Vec<(i32, i32)>> {
-        let start = start.into();
-        let end = end.into();
-        let p = pathfinding::directed::astar::astar(&start, |(nx, ny)| {
-            let (nx, ny) = (*nx, *ny);
-            let mut vec:Vec<((i32, i32), i32)> = Vec::with_capacity(4);
-            for p in [(nx - 1, ny), (nx + 1, ny), (nx, ny - 1), (nx, ny + 1)] {
-                if let Some(tile) = self.get(p) {
-                    if !visit(AStarVisit {
-                        index: p,
-                        cell: tile,
-                    }) {
-                        vec.push((p, 1));
-                    }
-                }
-            }
-            vec
-        }, |(nx, ny)|{
-            let (vx, vy) = ((nx - end.0).abs(), (ny - end.1).abs());
-            vx + vy
-        }, |n|{
-            n == &end
-        });
-        if let Some((vec, _)) = p {
-            return Some(vec);
+    /// Remove `T`, returning it if it was present
+    /// 
+    /// Chunks which become empty are removed from the grid
+    pub fn remove(&mut self, index: impl Into<(i32, i32)>) -> Option<T> {
+        let index:(i32, i32) = index.into();
+        let index:Index = index.into();
+        let chunk_index = index.chunk_index::<N>();
+        let local = index.local_index::<N>();
+        let chunk = self.chunks.get_mut(&chunk_index)?;
+        chunk.get_local(local)?.as_ref()?;
+        let chunk = make_mut(chunk);
+        let t = chunk.remove(local)?;
+        if chunk.is_empty() {
+            self.chunks.remove(&chunk_index);
         }
+        self.dirty.insert(chunk_index);
+        Some(t)
+    }
 
-        None
+    /// Gets the coordinate of the chunk containing the cell, see `ChunkIndex::coord`
+    pub fn chunk_of(&self, index: impl Into<(i32, i32)>) -> (i32, i32) {
+        let index:(i32, i32) = index.into();
+        Index::from(index).chunk_index::<N>().coord::<N>()
     }
 
-    /// Casts a ray from `start` to `end` and call a function `F` for each cell visited
+    /// Gets a immutable reference to the chunk at the chunk coordinate
+    pub fn chunk(&self, coord: impl Into<(i32, i32)>) -> Option<&Chunk<T, N>> {
+        self.chunks.get(&ChunkIndex::from_coord::<N>(coord)?).map(Arc::as_ref)
+    }
+
+    /// Gets an mutable reference to the chunk at the chunk coordinate
     /// 
-    /// The ray will be traced until `F` returns `false` or untill `end` has been reached
-    pub fn cast_ray<F:FnMut(RayVisit<T>)->bool>(&self, start:impl Into<(f32, f32)>, end:impl Into<(f32, f32)>, mut f:F) {
-        let start:(f32, f32) = start.into();
-        let end:(f32, f32) = end.into();
-        let start:Vec2 = start.into();
-        let end:Vec2 = end.into();
-        fn get_helper(cell_size:f32, pos:f32, dir:f32) -> (f32, f32, f32, f32) {
-            let tile = (pos / cell_size).floor();// + 1.0;
-            let dtile;
-            let dt;
-            let mut dir = dir;
-            if dir == 0.0 {
-                dir = 0.00001; // FIXME: avoid divide by zero but can be solved better
-            }
-            if dir > 0.0 {
-                dtile = 1.0;
-                dt = ((tile + 1.0) * cell_size - pos) / dir;
-            } else {
-                dtile = -1.0;
-                dt = (tile  * cell_size - pos) / dir;
-            }
-    
-            (tile, dtile, dt, dtile * cell_size / dir)
-        }
-        let v = end - start;
-        let dir = v.normalize_or_zero();
-        if dir.length() == 0.0 {
-            return;
+    /// Unlike `remove`, the chunk stays in the grid if all its cells are removed through the reference.
+    /// Uniform and palette chunks stay compact unless their cells are iterated mutably, see `Chunk::expand`
+    pub fn chunk_mut(&mut self, coord: impl Into<(i32, i32)>) -> Option<&mut Chunk<T, N>> {
+        let chunk_index = ChunkIndex::from_coord::<N>(coord)?;
+        let chunk = self.chunks.get_mut(&chunk_index)?;
+        self.dirty.insert(chunk_index);
+        Some(make_mut(chunk))
+    }
+
+    /// Returns `true` if the grid contains a chunk at the chunk coordinate
+    pub fn contains_chunk(&self, coord: impl Into<(i32, i32)>) -> bool {
+        self.chunk(coord).is_some()
+    }
+
+    /// Insert a chunk at its own coordinate, returning the chunk it replaced
+    /// 
+    /// Empty chunks are not inserted, but still replace the chunk at their coordinate
+    pub fn insert_chunk(&mut self, chunk: Chunk<T, N>) -> Option<Chunk<T, N>> {
+        self.dirty.insert(chunk.index);
+        if chunk.is_empty() {
+            return self.chunks.remove(&chunk.index).map(unwrap_chunk);
         }
-        let (mut tile_x, dtile_x, mut dt_x, ddt_x) = get_helper(1.0, start.x, dir.x);
-        let (mut tile_y, dtile_y, mut dt_y, ddt_y) = get_helper(1.0, start.y, dir.y);
-    
-        let mut t = 0.0;
-        if dir.x*dir.x + dir.y*dir.y > 0.0 {
-            loop {
-                if v.length() < t {
-                    break;
-                }
-                let index = (tile_x as i32, tile_y as i32);
-                if let Some(cell) = self.get(index) {
-                    if !f(RayVisit {index, cell, d:t, pos:(tile_x, tile_y) }) {
-                        break;
-                    }
-                } else {
-                    break;
-                }
-                if dt_x < dt_y {
-                    tile_x += dtile_x;
-                    let dt = dt_x;
-                    t += dt;
-                    dt_x = dt_x + ddt_x - dt;
-                    dt_y -= dt;
-                } else {
-                    tile_y += dtile_y;
-                    let dt = dt_y;
-                    t += dt;
-                    dt_x -= dt;
-                    dt_y = dt_y + ddt_y - dt;
-                }
-            }
-        } 
+        self.chunks.insert(chunk.index, Arc::new(chunk)).map(unwrap_chunk)
+    }
+
+    /// Remove the chunk at the chunk coordinate, returning it if it was present
+    /// 
+    /// Uniform and palette chunks are returned as they are, see `Chunk::expand`
+    pub fn remove_chunk(&mut self, coord: impl Into<(i32, i32)>) -> Option<Chunk<T, N>> {
+        let chunk_index = ChunkIndex::from_coord::<N>(coord)?;
+        self.generated.remove(&chunk_index);
+        let chunk = self.chunks.remove(&chunk_index)?;
+        self.dirty.insert(chunk_index);
+        Some(unwrap_chunk(chunk))
+    }
+
+    /// Perform the A-star algorithm
+    /// `F is a function which returns `true` when path is blocked and `false` when not blocked
+    /// 
+    /// Only the four orthogonal neighbours are expanded, see `astar_with` for diagonal movement and costs
+    pub fn astar<F:Fn(AStarVisit<T>)->bool>(&self, start:impl Into<(i32, i32)>, end:impl Into<(i32, i32)>, visit:F) -> Option<Vec<(i32, i32)>> {
+        let (path, _) = self.astar_with(start, end, AStarOptions::default(), |x|{
+            if visit(x) { None } else { Some(1) }
+        })?;
+        Some(path)
     }
 }
 
@@ -405,18 +989,18 @@ mod tests {
         let p1: Index = (0, 0).into();
         let p2: Index = (15, 15).into();
         assert_ne!(p1, p2);
-        assert_eq!(p1.chunk_index(), p2.chunk_index());
+        assert_eq!(p1.chunk_index::<CHUNK_SIZE>(), p2.chunk_index::<CHUNK_SIZE>());
 
         let p1: Index = (-7, -7).into();
         let p2: Index = (-9, -9).into();
         assert_ne!(p1, p2);
-        assert_eq!(p1.chunk_index(), p2.chunk_index());
+        assert_eq!(p1.chunk_index::<CHUNK_SIZE>(), p2.chunk_index::<CHUNK_SIZE>());
 
         let p1: Index = (CHUNK_SIZE as i32 * 10, CHUNK_SIZE as i32 * 10).into();
-        let p2 = p1.chunk_index().index();
+        let p2 = p1.chunk_index::<CHUNK_SIZE>().index::<CHUNK_SIZE>();
         assert_eq!(p1, p2);
         let p1: Index = (CHUNK_SIZE as i32 * -10, CHUNK_SIZE as i32 * -10).into();
-        let p2 = p1.chunk_index().index();
+        let p2 = p1.chunk_index::<CHUNK_SIZE>().index::<CHUNK_SIZE>();
         assert_eq!(p1, p2);
 
         let p1 = (1024, 5431);
@@ -454,7 +1038,7 @@ mod tests {
 
         let mut chunk = Chunk::default() as Chunk<Test>;
         let p = (-1024, -1024);
-        let chunk_index = Index::from(p).chunk_index();
+        let chunk_index = Index::from(p).chunk_index::<CHUNK_SIZE>();
         chunk.index = chunk_index;
 
         assert_eq!(chunk.top_left(), p);
@@ -540,7 +1124,7 @@ mod tests {
     #[test]
     fn grid_test4() {
         let mut grid = Grid::default() as Grid<(i32, i32)>;
-        let values = vec![(14, 0), (-1011,32), (-6654,-213), (5543,123), (65645, 12312), (0, 0)];
+        let values = [(14, 0), (-1011,32), (-6654,-213), (5543,123), (65645, 12312), (0, 0)];
         for v in values.iter() {
             grid.insert(v.to_owned(), v.to_owned());
         }
@@ -557,6 +1141,363 @@ mod tests {
         assert_eq!(grid.len(), values.len());
     }
 
+    #[test]
+    fn grid_remove_test() {
+        let mut grid = Grid::default() as Grid<(i32, i32)>;
+        let size = 20;
+        for y in -size..size {
+            for x in -size..size {
+                grid.insert((x, y), (x, y));
+            }
+        }
+        let chunks = grid.chunks.len();
+        assert_eq!(grid.remove((0, 0)), Some((0, 0)));
+        assert_eq!(grid.remove((0, 0)), None);
+        assert_eq!(grid.get((0, 0)), None);
+        assert_eq!(grid.len(), (size * size * 4) as usize - 1);
+        assert_eq!(grid.chunks.len(), chunks);
+
+        for y in -size..size {
+            for x in -size..size {
+                grid.remove((x, y));
+            }
+        }
+        assert!(grid.is_empty());
+        assert_eq!(grid.len(), 0);
+        assert_eq!(grid.chunks.len(), 0);
+        assert_eq!(grid.remove((1000, 1000)), None);
+
+        let mut chunk = Chunk::default() as Chunk<u8>;
+        chunk.insert(3, 1);
+        assert_eq!(chunk.remove(3), Some(1));
+        assert!(chunk.is_empty());
+        assert_eq!(chunk.inner.len(), 0);
+    }
+
+    #[test]
+    fn grid_entry_test() {
+        let mut grid = Grid::default() as Grid<i32>;
+        *grid.entry((1, 2)).or_insert(0) += 1;
+        *grid.entry((1, 2)).or_insert(0) += 1;
+        assert_eq!(grid.get((1, 2)), Some(&2));
+
+        grid.entry((-40, 7)).and_modify(|x| *x = 100).or_insert_with(|| 5);
+        assert_eq!(grid.get((-40, 7)), Some(&5));
+        grid.entry((-40, 7)).and_modify(|x| *x = 100).or_insert_with(|| 5);
+        assert_eq!(grid.get((-40, 7)), Some(&100));
+        assert_eq!(*grid.entry((3, 3)).or_default(), 0);
+        assert_eq!(grid.len(), 3);
+
+        match grid.entry((1, 2)) {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.index(), (1, 2));
+                assert_eq!(e.insert(7), 2);
+                assert_eq!(*e.get(), 7);
+            },
+            Entry::Vacant(_) => panic!("expected occupied entry"),
+        }
+
+        assert_eq!(grid.entry((1, 2)).remove(), Some(7));
+        assert_eq!(grid.entry((1, 2)).remove(), None);
+        assert_eq!(grid.entry((3, 3)).remove(), Some(0));
+        assert_eq!(grid.chunks.len(), 1);
+        assert_eq!(grid.entry((-40, 7)).remove(), Some(100));
+        assert!(grid.is_empty());
+        assert_eq!(grid.chunks.len(), 0);
+    }
+
+    #[test]
+    fn grid_iter_test() {
+        let mut grid = Grid::default() as Grid<(i32, i32)>;
+        let size = 20;
+        for y in -size..size {
+            for x in -size..size {
+                grid.insert((x, y), (x, y));
+            }
+        }
+        let len = grid.len();
+        assert_eq!(grid.iter().len(), len);
+        assert!(grid.iter().all(|(p, cell)| p == *cell));
+        let mut iter = grid.keys();
+        iter.next();
+        assert_eq!(iter.size_hint(), (len - 1, Some(len - 1)));
+        let mut keys:Vec<(i32, i32)> = grid.keys().collect();
+        let mut values:Vec<(i32, i32)> = grid.values().copied().collect();
+        keys.sort();
+        values.sort();
+        assert_eq!(keys, values);
+
+        for (p, cell) in grid.iter_mut() {
+            *cell = (p.0 * 2, p.1 * 2);
+        }
+        grid.values_mut().for_each(|cell| cell.0 += 1);
+        assert_eq!(grid.get((3, -4)), Some(&(7, -8)));
+
+        let owned:Vec<((i32, i32), (i32, i32))> = grid.into_iter().collect();
+        assert_eq!(owned.len(), len);
+        assert!(owned.iter().all(|(p, cell)| *cell == (p.0 * 2 + 1, p.1 * 2)));
+    }
+
+    #[test]
+    fn grid_from_iter_test() {
+        let size = 40;
+        let cells = (-size..size).flat_map(|y| (-size..size).map(move |x| ((x, y), (x, y))));
+        let mut grid:Grid<(i32, i32)> = cells.collect();
+        assert_eq!(grid.len(), (size * size * 4) as usize);
+        assert_eq!(grid.chunks.len(), 36);
+        assert!(grid.iter().all(|(p, cell)| p == *cell));
+
+        let snapshot = grid.snapshot();
+        grid.extend([((1000, 1000), (0, 0)), ((0, 0), (1, 1)), ((1001, 1000), (0, 0))]);
+        assert_eq!(grid.len(), (size * size * 4) as usize + 2);
+        assert_eq!(snapshot.get((0, 0)), Some(&(0, 0)));
+        assert_eq!(grid.get((0, 0)), Some(&(1, 1)));
+        assert_eq!(grid.get((1001, 1000)), Some(&(0, 0)));
+
+        // cells already in the grid are kept if the iterator panics
+        let cells = [((0, 0), (2, 2)), ((1, 0), (3, 3))].into_iter().enumerate().map(|(i, x)|if i == 0 { x } else { panic!() });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| grid.extend(cells)));
+        assert!(result.is_err());
+        assert_eq!((grid.get((0, 0)), grid.get((1, 0))), (Some(&(2, 2)), Some(&(1, 0))));
+    }
+
+    #[test]
+    fn grid_rect_test() {
+        let mut grid = Grid::default() as Grid<(i32, i32)>;
+        let size = 40;
+        for y in -size..size {
+            for x in -size..size {
+                if (x + y) % 3 != 0 {
+                    grid.insert((x, y), (x, y));
+                }
+            }
+        }
+        grid.insert((1000, 1000), (1000, 1000));
+
+        let rects = [((-5, -5), (5, 5)), ((-40, -40), (39, 39)), ((-17, 3), (15, 3)), ((20, -33), (-1, 17)), ((-100, -100), (100, 100))];
+        for (min, max) in rects {
+            let (x0, x1) = (min.0.min(max.0), min.0.max(max.0));
+            let (y0, y1) = (min.1.min(max.1), min.1.max(max.1));
+            let expected:Vec<(i32, i32)> = (y0..=y1)
+                .flat_map(|y| (x0..=x1).map(move |x| (x, y)))
+                .filter(|p| grid.get(*p).is_some())
+                .collect();
+            let visited:Vec<(i32, i32)> = grid.iter_rect(min, max).map(|(p, cell)| {
+                assert_eq!(p, *cell);
+                p
+            }).collect();
+            assert_eq!(visited, expected);
+            let visited:Vec<(i32, i32)> = grid.iter_rect_mut(min, max).map(|(p, _)| p).collect();
+            assert_eq!(visited, expected);
+        }
+
+        for (_, cell) in grid.iter_rect_mut((0, 0), (9, 9)) {
+            *cell = (-1, -1);
+        }
+        assert_eq!(grid.get((9, 8)), Some(&(-1, -1)));
+        assert_eq!(grid.get((10, 9)), Some(&(10, 9)));
+        assert_eq!(grid.iter_rect((i32::MIN, i32::MIN), (i32::MAX, i32::MAX)).count(), grid.len());
+        assert_eq!(grid.iter_rect((500, 500), (600, 600)).count(), 0);
+    }
+
+    #[test]
+    fn grid_ordered_test() {
+        let cells:Vec<((i32, i32), i32)> = (0..2000).map(|i| (((i * 7919) % 311 - 150, (i * 104729) % 257 - 128), i)).collect();
+        let mut grid1 = Grid::default() as Grid<i32>;
+        let mut grid2 = Grid::default() as Grid<i32>;
+        for (p, i) in cells.iter() {
+            grid1.insert(*p, *i);
+        }
+        for (p, i) in cells.iter().rev() {
+            grid2.entry(*p).or_insert(*i);
+        }
+
+        let chunks:Vec<(i32, i32)> = grid1.chunks_ordered().map(|x| x.top_left()).collect();
+        let mut sorted = chunks.clone();
+        sorted.sort_by_key(|p| (p.1, p.0));
+        assert_eq!(chunks, sorted);
+
+        let ordered1:Vec<((i32, i32), &i32)> = grid1.iter_ordered().collect();
+        let ordered2:Vec<((i32, i32), &i32)> = grid2.iter_ordered().collect();
+        assert_eq!(ordered1, ordered2);
+        assert_eq!(ordered1.len(), grid1.len());
+        assert_eq!(bincode::serialize(&grid1).unwrap(), bincode::serialize(&grid2).unwrap());
+
+        grid1.iter_ordered_mut().for_each(|(_, cell)| *cell = 0);
+        assert!(grid1.values().all(|cell| *cell == 0));
+    }
+
+    #[test]
+    fn grid_chunk_size_test() {
+        fn test<const N: usize>() {
+            let mut grid = Grid::default() as Grid<(i32, i32), N>;
+            let size = 70;
+            for y in -size..size {
+                for x in -size..size {
+                    grid.insert((x, y), (x, y));
+                }
+            }
+            let chunks = 2 * (size as usize).div_ceil(N);
+            assert_eq!(grid.chunks.len(), chunks * chunks);
+            assert_eq!(grid.len(), (size * size * 4) as usize);
+            assert!(grid.iter().all(|(p, cell)| p == *cell));
+            assert_eq!(grid.iter_rect((-3, -3), (2, 2)).count(), 36);
+            for chunk in &grid {
+                assert_eq!(chunk.top_left().0.rem_euclid(N as i32), 0);
+                assert_eq!(chunk.bottom_right().0 - chunk.top_left().0, N as i32 - 1);
+                for (p, cell) in chunk {
+                    assert_eq!(p, *cell);
+                }
+            }
+            let bincoded = bincode::serialize(&grid).unwrap();
+            let grid2:Grid<(i32, i32), N> = bincode::deserialize(&bincoded).unwrap();
+            assert_eq!(grid2.get((-size, size - 1)), Some(&(-size, size - 1)));
+            for y in -size..size {
+                for x in -size..size {
+                    assert_eq!(grid.remove((x, y)), Some((x, y)));
+                }
+            }
+            assert!(grid.is_empty());
+            assert_eq!(grid.chunks.len(), 0);
+        }
+        test::<8>();
+        test::<16>();
+        test::<64>();
+        test::<256>();
+    }
+
+    #[test]
+    fn astar_with_test() {
+        let mut grid = Grid::default() as Grid<bool>;
+        for y in 0..8 {
+            for x in 0..8 {
+                grid.insert((x, y), false);
+            }
+        }
+        let cost = |x:AStarVisit<bool>| {
+            if *x.cell { None } else if x.is_diagonal() { Some(14) } else { Some(10) }
+        };
+
+        let (path, total) = grid.astar_with((0, 0), (7, 7), AStarOptions::diagonal(CornerCutting::Never), cost).unwrap();
+        assert_eq!(path.len(), 8);
+        assert_eq!(total, 7 * 14);
+        let (path, total) = grid.astar_with((0, 0), (7, 3), AStarOptions::diagonal(CornerCutting::Never), cost).unwrap();
+        assert_eq!(path.len(), 8);
+        assert_eq!(total, 3 * 14 + 4 * 10);
+        let (path, total) = grid.astar_with((0, 0), (7, 7), AStarOptions { orthogonal_cost:10, ..Default::default() }, cost).unwrap();
+        assert_eq!(path.len(), 15);
+        assert_eq!(total, 14 * 10);
+
+        let mut grid = Grid::default() as Grid<bool>;
+        grid.extend([((0, 0), false), ((1, 1), false), ((1, 0), true), ((0, 1), true)]);
+        for (corner_cutting, expected) in [(CornerCutting::Allow, true), (CornerCutting::IfOneOpen, false), (CornerCutting::Never, false)] {
+            let path = grid.astar_with((0, 0), (1, 1), AStarOptions::diagonal(corner_cutting), cost);
+            assert_eq!(path.is_some(), expected);
+        }
+        grid.insert((1, 0), false);
+        let (path, _) = grid.astar_with((0, 0), (1, 1), AStarOptions::diagonal(CornerCutting::IfOneOpen), cost).unwrap();
+        assert_eq!(path, vec![(0, 0), (1, 1)]);
+        let (path, _) = grid.astar_with((0, 0), (1, 1), AStarOptions::diagonal(CornerCutting::Never), cost).unwrap();
+        assert_eq!(path, vec![(0, 0), (1, 0), (1, 1)]);
+
+        let options = AStarOptions { heuristic:Heuristic::Euclidean, ..AStarOptions::diagonal(CornerCutting::Allow) };
+        assert_eq!(options.estimate((0, 0), (3, 4)), 49);
+        assert!(options.estimate((0, 0), (100, 100)) <= 1400);
+
+        // large costs and distances saturate instead of overflowing
+        let options = AStarOptions { orthogonal_cost:u32::MAX, ..AStarOptions::diagonal(CornerCutting::Allow) };
+        assert_eq!(options.estimate((0, 0), (2, 1)), u32::MAX);
+        let options = AStarOptions { heuristic:Heuristic::Manhattan, ..AStarOptions::default() };
+        assert_eq!(options.estimate((i32::MIN, i32::MIN), (i32::MAX, i32::MAX)), u32::MAX);
+    }
+
+    #[test]
+    fn dijkstra_map_test() {
+        let mut grid = Grid::default() as Grid<bool>;
+        for y in 0..10 {
+            for x in 0..10 {
+                grid.insert((x, y), x == 5 && y != 9);
+            }
+        }
+        let cost = |x:AStarVisit<bool>| if *x.cell { None } else { Some(1) };
+        let map = grid.dijkstra_map([(0, 0), (9, 0)], u32::MAX, Movement::Orthogonal, cost);
+        assert_eq!(map.get((0, 0)), Some(&0));
+        assert_eq!(map.get((9, 0)), Some(&0));
+        assert_eq!(map.get((4, 0)), Some(&4));
+        assert_eq!(map.get((6, 0)), Some(&3));
+        assert_eq!(map.get((5, 0)), None);
+        assert_eq!(map.get((5, 9)), Some(&13));
+        assert_eq!(map.len(), 100 - 9);
+
+        let path = map.walk_downhill((4, 9), Movement::Orthogonal);
+        assert_eq!(path.len(), 14);
+        assert_eq!(*path.last().unwrap(), (0, 0));
+        assert!(path.windows(2).all(|w| map.get(w[0]) > map.get(w[1])));
+        assert_eq!(map.walk_downhill((5, 5), Movement::Orthogonal), vec![]);
+        assert_eq!(map.downhill((0, 0), Movement::Orthogonal), None);
+
+        let map = grid.dijkstra_map([(0, 0)], 3, Movement::Diagonal(CornerCutting::Never), cost);
+        assert_eq!(map.get((3, 3)), Some(&3));
+        assert_eq!(map.get((4, 0)), None);
+        assert_eq!(map.len(), 16);
+
+        // neighbours beyond the edges of the grid are skipped
+        let corner:Grid<u32> = [((i32::MAX, i32::MIN), 0), ((i32::MAX - 1, i32::MIN), 0), ((i32::MAX, i32::MIN + 1), 0)].into_iter().collect();
+        let map = corner.dijkstra_map([(i32::MAX, i32::MIN)], 10, Movement::Diagonal(CornerCutting::Allow), |_| Some(1));
+        assert_eq!(map.len(), 3);
+        let path = corner.astar((i32::MAX - 1, i32::MIN), (i32::MAX, i32::MIN + 1), |_| false).unwrap();
+        assert_eq!(path.len(), 3);
+    }
+
+    #[test]
+    fn field_of_view_test() {
+        let mut grid = Grid::default() as Grid<bool>;
+        for y in -20..=20 {
+            for x in -20..=20 {
+                grid.insert((x, y), false);
+            }
+        }
+        let visible = grid.field_of_view((0, 0), 5, |x| *x);
+        assert_eq!(visible.len(), grid.keys().filter(|(x, y)| x * x + y * y <= 25).count());
+
+        // a pillar casts a shadow
+        grid.insert((2, 0), true);
+        let visible = grid.field_of_view((0, 0), 10, |x| *x);
+        assert!(visible.get((1, 0)).is_some());
+        assert!(visible.get((2, 0)).is_some());
+        assert!(visible.get((3, 0)).is_none());
+        assert!(visible.get((8, 0)).is_none());
+        assert!(visible.get((8, 4)).is_some());
+        assert!(visible.get((0, 10)).is_some());
+        assert!(visible.get((0, 11)).is_none());
+
+        // missing cells block the view
+        grid.remove((0, 3));
+        let visible = grid.field_of_view((0, 0), 10, |x| *x);
+        assert!(visible.get((0, 2)).is_some());
+        assert!(visible.get((0, 3)).is_none());
+        assert!(visible.get((0, 5)).is_none());
+
+        // symmetry
+        let mut grid = Grid::default() as Grid<bool>;
+        for y in -12..=12 {
+            for x in -12..=12 {
+                grid.insert((x, y), (x * 7 + y * 13).rem_euclid(11) == 0);
+            }
+        }
+        let origin = (0, 0);
+        grid.insert(origin, false);
+        let visible = grid.field_of_view(origin, 8, |x| *x);
+        for (p, _) in visible.iter() {
+            if !*grid.get(p).unwrap() {
+                assert!(grid.field_of_view(p, 8, |x| *x).get(origin).is_some());
+            }
+        }
+        let mut visits = 0;
+        grid.visit_field_of_view(origin, 8, |x| *x, |_, _| visits += 1);
+        assert_eq!(visits, visible.len());
+    }
+
     #[test]
     fn grid_serde_test() {
         let mut grid = Grid::default() as Grid<(i32, i32)>;
@@ -580,6 +1521,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn grid_serde_validation_test() {
+        // chunks with an index outside of the grid are rejected
+        let mut chunk = Chunk::<u32, 16>::new((0, 0));
+        chunk.insert(0, 1);
+        chunk.index.x = u32::MAX;
+        assert!(bincode::deserialize::<Chunk<u32, 16>>(&bincode::serialize(&chunk).unwrap()).is_err());
+        chunk.index = ChunkIndex::from_coord::<16>((i32::MAX / 16, 0)).unwrap();
+        assert!(bincode::deserialize::<Chunk<u32, 16>>(&bincode::serialize(&chunk).unwrap()).is_ok());
+
+        // chunks stored under the index of another chunk are rejected
+        let mut grid = Grid::default() as Grid<u32, 16>;
+        grid.insert((0, 0), 5);
+        assert!(bincode::deserialize::<Grid<u32, 16>>(&bincode::serialize(&grid).unwrap()).is_ok());
+        let chunk = grid.chunks.remove(&Index::from((0, 0)).chunk_index::<16>()).unwrap();
+        grid.chunks.insert(Index::from((16, 0)).chunk_index::<16>(), chunk);
+        assert!(bincode::deserialize::<Grid<u32, 16>>(&bincode::serialize(&grid).unwrap()).is_err());
+    }
+
     #[test]
     fn raycast_test() {
         let mut grid = Grid::default() as Grid<bool>;
@@ -604,6 +1564,810 @@ mod tests {
         assert_eq!(last_pos_before_hit, (3.0, 4.0));
     }
 
+    #[test]
+    fn raycast_hit_test() {
+        let mut grid = Grid::default() as Grid<bool>;
+        for y in 0..8 {
+            for x in 0..8 {
+                grid.insert((x, y), x == 4 || y == 6);
+            }
+        }
+        let hit = grid.raycast_first((0.5, 0.5), (7.5, 0.5), |x| *x).unwrap();
+        assert_eq!(hit.index, (4, 0));
+        assert_eq!(hit.point, (4.0, 0.5));
+        assert_eq!(hit.normal, (-1, 0));
+        assert_eq!(hit.d, 3.5);
+
+        let hit = grid.raycast_first((7.5, 0.5), (5.5, 0.5), |x| *x);
+        assert!(hit.is_none());
+
+        let hit = grid.raycast_first((1.5, 1.5), (1.5, 7.5), |x| *x).unwrap();
+        assert_eq!(hit.index, (1, 6));
+        assert_eq!(hit.point, (1.5, 6.0));
+        assert_eq!(hit.normal, (0, -1));
+
+        let hit = grid.raycast_first((6.5, 2.5), (2.5, 2.5), |x| *x).unwrap();
+        assert_eq!(hit.index, (4, 2));
+        assert_eq!(hit.point, (5.0, 2.5));
+        assert_eq!(hit.normal, (1, 0));
+        assert_eq!(hit.d, 1.5);
+
+        let hit = grid.raycast_first((4.5, 2.5), (6.5, 2.5), |x| *x).unwrap();
+        assert_eq!(hit.normal, (0, 0));
+        assert_eq!(hit.d, 0.0);
+
+        let mut visits = Vec::new();
+        grid.cast_ray((0.5, 0.5), (2.25, 0.5), |v| {
+            visits.push((v.index, v.d, v.d_exit, v.point));
+            true
+        });
+        assert_eq!(visits, vec![
+            ((0, 0), 0.0, 0.5, (0.5, 0.5)),
+            ((1, 0), 0.5, 1.5, (1.0, 0.5)),
+            ((2, 0), 1.5, 1.75, (2.0, 0.5)),
+        ]);
+    }
+
+    #[test]
+    fn raycast_sparse_test() {
+        let mut grid = Grid::default() as Grid<u8>;
+        grid.insert((0, 0), 0);
+        grid.insert((3, 1), 1);
+        grid.insert((100, 40), 2);
+
+        let mut hits = Vec::new();
+        let mut visits = 0;
+        grid.cast_ray_sparse((0.5, 0.5), (120.5, 48.5), |v| {
+            visits += 1;
+            if let Some(cell) = v.cell {
+                hits.push((v.index, *cell));
+            }
+            true
+        });
+        assert_eq!(hits, vec![((0, 0), 0), ((3, 1), 1), ((100, 40), 2)]);
+        assert!(visits < 120 + 48);
+
+        let mut last = None;
+        grid.cast_ray_sparse((0.5, 0.5), (5.5, 0.5), |v| {
+            last = Some(v.index);
+            v.cell.is_none_or(|x| *x != 1)
+        });
+        assert_eq!(last, Some((5, 0)));
+
+        // skipping empty chunks visits the same cells as stepping through them
+        let mut grid = Grid::default() as Grid<(i32, i32)>;
+        for (x, y) in [(-20, -20), (-1, 3), (5, 17), (40, 33), (33, 40), (63, 63), (17, 5)] {
+            for dy in 0..3 {
+                for dx in 0..3 {
+                    grid.insert((x + dx, y + dy), (x + dx, y + dy));
+                }
+            }
+        }
+        for (start, end) in [((-19.5, -19.3), (64.2, 64.7)), ((63.9, 63.1), (-20.5, -19.5)), ((-10.0, 40.25), (70.0, 5.5)), ((18.5, -30.0), (18.5, 60.0))] {
+            let mut expected = Vec::new();
+            for step in Ray::new(start, end).unwrap() {
+                if let Some(cell) = grid.get(step.index) {
+                    expected.push((*cell, step.d));
+                }
+            }
+            let mut hits = Vec::new();
+            grid.cast_ray_sparse(start, end, |v| {
+                if let Some(cell) = v.cell {
+                    hits.push((*cell, v.d));
+                }
+                true
+            });
+            assert_eq!(hits.len(), expected.len());
+            for (a, b) in hits.iter().zip(expected.iter()) {
+                assert_eq!(a.0, b.0);
+                assert!((a.1 - b.1).abs() < 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn transform_test() {
+        use glam::Vec2;
+        let transform = GridTransform::with_cell_size(Vec2::new(100.0, -50.0), Vec2::new(8.0, 32.0));
+        assert_eq!(transform.world_to_cell(Vec2::new(100.0, -50.0)), (0, 0));
+        assert_eq!(transform.world_to_cell(Vec2::new(99.9, -50.0)), (-1, 0));
+        assert_eq!(transform.world_to_cell(Vec2::new(117.0, 15.0)), (2, 2));
+        assert_eq!(transform.cell_to_world((2, 2)), Vec2::new(116.0, 14.0));
+        assert_eq!(transform.cell_center((2, 2)), Vec2::new(120.0, 30.0));
+        assert_eq!(transform.cell_rect((-1, 0)), (Vec2::new(92.0, -50.0), Vec2::new(100.0, -18.0)));
+        assert_eq!(transform.world_to_cell(transform.cell_center((-7, 13))), (-7, 13));
+
+        let mut grid = Grid::default() as Grid<bool>;
+        for y in 0..8 {
+            for x in 0..8 {
+                grid.insert((x, y), x == 4);
+            }
+        }
+        assert_eq!(grid.get_at_world(&transform, Vec2::new(134.0, 0.0)), Some(&true));
+        assert_eq!(grid.get_at_world(&transform, Vec2::new(0.0, 0.0)), None);
+        *grid.get_at_world_mut(&transform, Vec2::new(101.0, -49.0)).unwrap() = true;
+        assert_eq!(grid.get((0, 0)), Some(&true));
+        assert_eq!(grid.iter_rect_world(&transform, Vec2::new(100.0, -50.0), Vec2::new(115.0, 13.0)).count(), 4);
+        assert_eq!(grid.iter_rect_world_mut(&transform, Vec2::new(100.0, -50.0), Vec2::new(116.0, 14.0)).count(), 9);
+
+        let hit = grid.raycast_first_world(&transform, Vec2::new(108.5, 0.0), Vec2::new(160.0, 0.0), |x| *x).unwrap();
+        assert_eq!(hit.index, (4, 1));
+        assert_eq!(hit.point, (132.0, 0.0));
+        assert_eq!(hit.normal, (-1, 0));
+        assert!((hit.d - 23.5).abs() < 0.0001);
+
+        let mut last = None;
+        grid.cast_ray_world(&transform, Vec2::new(101.0, -49.0), Vec2::new(101.0, 100.0), |v| {
+            last = Some((v.index, v.point, v.d));
+            true
+        });
+        let (index, point, d) = last.unwrap();
+        assert_eq!(index, (0, 4));
+        assert!((point.1 - 78.0).abs() < 0.001);
+        assert!((d - 127.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn grid_chunk_access_test() {
+        let mut grid = Grid::default() as Grid<u32, 16>;
+        assert_eq!(grid.chunk_of((0, 0)), (0, 0));
+        assert_eq!(grid.chunk_of((-1, 15)), (-1, 0));
+        assert_eq!(grid.chunk_of((48, -17)), (3, -2));
+        assert_eq!(grid.chunk_of((i32::MIN, i32::MAX)), (i32::MIN / 16, i32::MAX / 16));
+
+        grid.insert((50, -20), 7);
+        assert!(grid.contains_chunk((3, -2)));
+        assert!(!grid.contains_chunk((0, 0)));
+        assert!(!grid.contains_chunk((i32::MAX, 0)));
+        let chunk = grid.chunk((3, -2)).unwrap();
+        assert_eq!(chunk.coord(), (3, -2));
+        assert_eq!(chunk.top_left(), (48, -32));
+        assert_eq!(chunk.len(), 1);
+
+        grid.chunk_mut((3, -2)).unwrap().insert(0, 8);
+        assert_eq!(grid.get((48, -32)), Some(&8));
+
+        let mut chunk = Chunk::new((-1, -1));
+        chunk.insert(16 * 16 - 1, 9);
+        assert!(grid.insert_chunk(chunk).is_none());
+        assert_eq!(grid.get((-1, -1)), Some(&9));
+        assert_eq!(grid.len(), 3);
+        assert!(grid.insert_chunk(Chunk::new((-1, -1))).is_some());
+        assert!(!grid.contains_chunk((-1, -1)));
+
+        let chunk = grid.remove_chunk((3, -2)).unwrap();
+        assert_eq!(chunk.len(), 2);
+        assert!(grid.is_empty());
+        assert!(grid.remove_chunk((3, -2)).is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "file-store")]
+    fn streaming_grid_test() {
+        let dir = std::env::temp_dir().join(format!("endlessgrid_stream_{}", std::process::id()));
+        let store = FileChunkStore::new(&dir).unwrap();
+        let mut grid:StreamingGrid<u32, FileChunkStore, 16> = StreamingGrid::new(store, 1);
+        grid.update([(0, 0)]).unwrap();
+        assert!(grid.is_resident((-1, -1)) && grid.is_resident((1, 1)));
+        assert!(grid.grid().dirty_chunks().is_empty());
+        assert!(!grid.is_resident((2, 0)));
+        grid.grid_mut().insert((5, 5), 1);
+        grid.grid_mut().insert((-10, 3), 2);
+        grid.grid_mut().insert((100, 0), 3);
+
+        // moving away evicts and saves all chunks
+        let mut events = Vec::new();
+        grid.update_with([(1000, 0)], |e|events.push(e)).unwrap();
+        assert!(events.contains(&StreamEvent::Unloaded((0, 0))));
+        assert!(events.contains(&StreamEvent::Unloaded((6, 0))));
+        assert!(events.contains(&StreamEvent::Loaded((62, 0))));
+        assert!(grid.grid().is_empty());
+        assert!(grid.grid_mut().take_changes().is_empty());
+        assert!(grid.store().path((0, 0)).exists());
+        assert!(grid.store().path((6, 0)).exists());
+        assert!(!grid.store().path((1, 0)).exists());
+
+        // evicting a chunk created outside of the resident area keeps the stored cells
+        grid.grid_mut().insert((102, 0), 4);
+        grid.update([(1000, 0)]).unwrap();
+
+        // loading on demand merges the stored cells with cells written before the chunk was loaded
+        grid.grid_mut().insert((101, 0), 5);
+        assert_eq!(grid.get_or_load((100, 0)).unwrap(), Some(&3));
+        assert_eq!(grid.grid().get((101, 0)), Some(&5));
+        assert_eq!(grid.grid().get((102, 0)), Some(&4));
+        assert!(grid.is_resident((6, 0)));
+
+        // coming back loads the chunks again, chunks emptied while resident are removed from the store
+        grid.update([(0, 0)]).unwrap();
+        assert_eq!(grid.grid().get((5, 5)), Some(&1));
+        assert_eq!(grid.grid().get((-10, 3)), Some(&2));
+        assert_eq!(grid.grid().get((100, 0)), None);
+        assert!(grid.grid().dirty_chunks().is_empty());
+        grid.grid_mut().remove((5, 5));
+        grid.update([(1000, 0)]).unwrap();
+        assert!(!grid.store().path((0, 0)).exists());
+        assert!(grid.store().path((-1, 0)).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Keeps chunks in memory and records every chunk written to or removed from the store
+    #[derive(Default)]
+    struct MemoryStore {
+        chunks:HashMap<(i32, i32), Chunk<u32, 16>>,
+        written:Vec<(i32, i32)>,
+    }
+
+    impl ChunkStore<u32, 16> for MemoryStore {
+        type Error = ();
+
+        fn load(&mut self, coord:(i32, i32)) -> Result<Option<Chunk<u32, 16>>, Self::Error> {
+            Ok(self.chunks.get(&coord).cloned())
+        }
+
+        fn save(&mut self, chunk:&Chunk<u32, 16>) -> Result<(), Self::Error> {
+            self.written.push(chunk.coord());
+            self.chunks.insert(chunk.coord(), chunk.clone());
+            Ok(())
+        }
+
+        fn remove(&mut self, coord:(i32, i32)) -> Result<(), Self::Error> {
+            self.written.push(coord);
+            self.chunks.remove(&coord);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn streaming_grid_save_test() {
+        let mut grid:StreamingGrid<u32, MemoryStore, 16> = StreamingGrid::new(MemoryStore::default(), 1);
+        grid.update([(0, 0)]).unwrap();
+        grid.grid_mut().insert((5, 5), 1);
+        grid.grid_mut().insert((20, 0), 2);
+
+        // only modified chunks are written, even if the dirty flags have been cleared
+        grid.grid_mut().clear_dirty();
+        grid.flush().unwrap();
+        assert_eq!(grid.store().written, vec![(0, 0), (1, 0)]);
+        grid.flush().unwrap();
+        grid.update([(1000, 0)]).unwrap();
+        assert_eq!(grid.store().written.len(), 2);
+
+        // loading and evicting unchanged chunks writes nothing
+        grid.update([(0, 0)]).unwrap();
+        assert_eq!(grid.grid().get((20, 0)), Some(&2));
+        grid.update([(1000, 0)]).unwrap();
+        assert_eq!(grid.store().written.len(), 2);
+
+        // chunks created outside of the resident area are merged with the stored chunk and written back
+        grid.grid_mut().insert((6, 5), 3);
+        grid.grid_mut().take_changes();
+        grid.update([(1000, 0)]).unwrap();
+        assert_eq!(grid.store().written[2..], [(0, 0)]);
+        assert_eq!(grid.store().chunks[&(0, 0)].len(), 2);
+
+        // chunks emptied while resident are removed from the store
+        grid.update([(0, 0)]).unwrap();
+        grid.grid_mut().remove_chunk((1, 0));
+        grid.update([(1000, 0)]).unwrap();
+        assert_eq!(grid.store().written[3..], [(1, 0)]);
+        assert!(!grid.store().chunks.contains_key(&(1, 0)));
+
+        // generated chunks are saved, chunks emptied after being generated are kept as an empty record
+        let generator = |coord:(i32, i32), chunk:&mut Chunk<u32, 16>|if coord.0 == 5 {
+            chunk.insert(0, 9);
+        };
+        grid.update([(80, 0)]).unwrap();
+        grid.grid_mut().ensure_region((64, 0), (96, 0), &generator);
+        grid.grid_mut().remove((80, 0));
+        grid.update([(1000, 0)]).unwrap();
+        assert!((4..=6).all(|x|grid.store().chunks[&(x, 0)].is_empty()));
+        grid.update([(80, 0)]).unwrap();
+        assert!(!grid.grid_mut().ensure_chunk((5, 0), &generator));
+        assert!(!grid.grid_mut().ensure_chunk((4, 0), &generator));
+        assert_eq!(grid.grid().get((80, 0)), None);
+    }
+
+    #[test]
+    fn chunk_generator_test() {
+        // walls on every cell with a random value divisible by 4, except along the x axis
+        let generator = |coord:(i32, i32), chunk:&mut Chunk<bool, 16>| {
+            let mut state = chunk_seed(42, coord);
+            for local in 0..16 * 16 {
+                state = chunk_seed(state, (0, 0));
+                let y = chunk.top_left().1 + (local / 16) as i32;
+                chunk.insert(local, y != 0 && state.is_multiple_of(4));
+            }
+        };
+        assert_eq!(chunk_seed(42, (3, -2)), chunk_seed(42, (3, -2)));
+        assert_ne!(chunk_seed(42, (3, -2)), chunk_seed(42, (-2, 3)));
+
+        let mut grid = Grid::default() as Grid<bool, 16>;
+        assert_eq!(grid.get((5, 0)), None);
+        assert_eq!(grid.get_or_generate((5, 0), &generator), Some(&false));
+        assert!(grid.contains_chunk((0, 0)));
+        assert!(!grid.ensure_chunk((0, 0), &generator));
+
+        grid.ensure_region((-20, -20), (20, 20), &generator);
+        assert_eq!(grid.chunks.len(), 16);
+        let mut other = Grid::default() as Grid<bool, 16>;
+        other.ensure_region((-20, -20), (20, 20), &generator);
+        assert!(grid.iter().all(|(index, cell)|other.get(index) == Some(cell)));
+
+        let mut visited = 0;
+        grid.cast_ray_generated((0.5, 0.5), (200.5, 0.5), &generator, |x|{
+            visited += 1;
+            !*x.cell
+        });
+        assert_eq!(visited, 201);
+        assert!(grid.contains_chunk((12, 0)));
+
+        let (path, cost) = grid.astar_generated((0, 0), (100, 0), AStarOptions::default(), &generator, |x|(!*x.cell).then_some(1)).unwrap();
+        assert_eq!(cost, 100);
+        assert_eq!(path.len(), 101);
+        assert!(grid.dirty_chunks().is_empty());
+
+        // empty chunks are only generated once
+        let calls = std::cell::Cell::new(0);
+        let empty = |_:(i32, i32), _:&mut Chunk<bool, 16>|calls.set(calls.get() + 1);
+        assert!(grid.ensure_chunk((-50, 0), &empty));
+        assert!(!grid.ensure_chunk((-50, 0), &empty));
+        assert_eq!((calls.get(), grid.get((-800, 0))), (1, None));
+        grid.remove_chunk((-50, 0));
+        assert!(grid.ensure_chunk((-50, 0), &empty));
+
+        // generated chunks are remembered by serialized grids
+        let mut copy:Grid<bool, 16> = bincode::deserialize(&bincode::serialize(&grid).unwrap()).unwrap();
+        assert!(!copy.ensure_chunk((-50, 0), &empty));
+        let Compact(mut copy):Compact<Grid<bool, 16>> = bincode::deserialize(&bincode::serialize(&Compact(&grid)).unwrap()).unwrap();
+        assert!(!copy.ensure_chunk((-50, 0), &empty));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "file-store")]
+    fn region_file_test() {
+        let mut region = RegionFile::create(std::io::Cursor::new(Vec::new()), 16, (0, -1), 4).unwrap();
+        let mut a = Chunk::<u32, 16>::new((1, -1));
+        a.insert(3, 1);
+        let mut b = Chunk::<u32, 16>::new((3, -4));
+        b.insert(5, 2);
+        region.write_chunk(&a).unwrap();
+        region.write_chunk(&b).unwrap();
+        assert!(region.contains_chunk((1, -1)));
+        assert!(!region.contains_chunk((0, -1)));
+        assert!(region.read_chunk::<u32, 8>((1, -1)).is_err());
+        assert!(RegionFile::create(std::io::Cursor::new(Vec::new()), 16, (0, 0), 0).is_err());
+        assert!(RegionFile::create(std::io::Cursor::new(Vec::new()), 16, (0, 0), 70000).is_err());
+        let header = [&b"EGRF"[..], &1u32.to_le_bytes(), &16u32.to_le_bytes(), &0u32.to_le_bytes(), &[0; 8]].concat();
+        assert!(RegionFile::open(std::io::Cursor::new(header)).is_err());
+        assert!(RegionFile::open(std::io::Cursor::new(vec![0; 1024])).is_err());
+
+        // chunks of other regions are rejected instead of overwriting the chunk in the same slot
+        let mut c = Chunk::<u32, 16>::new((5, -1));
+        c.insert(0, 3);
+        assert!(region.write_chunk(&c).is_err());
+        assert!(!region.contains_chunk((5, -1)));
+        assert!(region.read_chunk::<u32, 16>((5, -1)).is_err());
+        assert!(region.remove_chunk((5, -1)).is_err());
+        assert_eq!(region.read_chunk::<u32, 16>((1, -1)).unwrap().unwrap().get_local(3), Some(&Some(1)));
+
+        // overwriting a chunk of equal size is done in place
+        let len = region.into_inner().into_inner().len();
+
+        let mut region = RegionFile::create(std::io::Cursor::new(Vec::new()), 16, (0, -1), 4).unwrap();
+        region.write_chunk(&a).unwrap();
+        region.write_chunk(&b).unwrap();
+        a.insert(3, 10);
+        region.write_chunk(&a).unwrap();
+        region.remove_chunk((3, -4)).unwrap();
+        let bytes = region.into_inner().into_inner();
+        assert_eq!(bytes.len(), len);
+
+        let mut region = RegionFile::open(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!((region.chunk_size(), region.region_size(), region.region()), (16, 4, (0, -1)));
+        let chunk:Chunk<u32, 16> = region.read_chunk((1, -1)).unwrap().unwrap();
+        assert_eq!(chunk.get_local(3), Some(&Some(10)));
+        assert!(region.read_chunk::<u32, 16>((3, -4)).unwrap().is_none());
+
+        // region store used for streaming
+        let dir = std::env::temp_dir().join(format!("endlessgrid_region_{}", std::process::id()));
+        let store = RegionStore::with_region_size(&dir, 4).unwrap();
+        let mut grid:StreamingGrid<u32, RegionStore, 16> = StreamingGrid::new(store, 0);
+        grid.update([(0, 0)]).unwrap();
+        grid.grid_mut().insert((1, 2), 3);
+        grid.grid_mut().insert((100, 2), 4);
+        grid.update([(1000, 1000)]).unwrap();
+        assert!(grid.store().path((0, 0)).exists());
+        assert!(grid.store().path((1, 0)).exists());
+        assert!(!grid.store().path((-1, 0)).exists());
+        let mut store = grid.into_store().unwrap();
+        store.set_max_open_regions(1);
+        let mut grid:StreamingGrid<u32, RegionStore, 16> = StreamingGrid::new(store, 0);
+        assert_eq!(grid.get_or_load((100, 2)).unwrap(), Some(&4));
+        assert_eq!(grid.get_or_load((1, 2)).unwrap(), Some(&3));
+        assert_eq!(grid.store().open_regions(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn grid_dirty_test() {
+        let mut grid = Grid::default() as Grid<u32, 16>;
+        grid.insert((1, 1), 1);
+        grid.insert((-1, 20), 2);
+        grid.insert((40, 0), 3);
+        assert_eq!(grid.dirty_chunks(), vec![(0, 0), (2, 0), (-1, 1)]);
+        let mut replica = Grid::default() as Grid<u32, 16>;
+        grid.take_changes().apply(&mut replica);
+        assert!(grid.dirty_chunks().is_empty());
+        assert!(grid.take_changes().is_empty());
+
+        // reads and misses do not mark chunks dirty
+        assert_eq!(grid.get((1, 1)), Some(&1));
+        assert_eq!(grid.get_mut((2, 2)), None);
+        assert_eq!(grid.remove((3, 3)), None);
+        assert_eq!(grid.entry((500, 500)).remove(), None);
+        assert_eq!(grid.entry((1, 1)).index(), (1, 1));
+        assert!(grid.dirty_chunks().is_empty());
+
+        *grid.get_mut((1, 1)).unwrap() = 10;
+        grid.remove((40, 0));
+        assert!(grid.is_chunk_dirty((0, 0)));
+        assert!(!grid.is_chunk_dirty((-1, 1)));
+        let changes = grid.take_changes();
+        assert_eq!(changes.chunks.len(), 1);
+        assert_eq!(changes.removed, vec![(2, 0)]);
+        changes.apply(&mut replica);
+        assert!(replica.iter().all(|(index, cell)|grid.get(index) == Some(cell)));
+        assert_eq!(replica.len(), grid.len());
+
+        grid.iter_rect_mut((-5, 16), (-1, 31)).for_each(|(_, x)|*x += 1);
+        assert_eq!(grid.dirty_chunks(), vec![(-1, 1)]);
+        grid.clear_dirty();
+        grid.values_mut().for_each(|x|*x += 1);
+        assert_eq!(grid.dirty_chunks(), vec![(0, 0), (-1, 1)]);
+        grid.clear_dirty();
+        grid.entry((5, 5)).or_insert(5);
+        grid.extend([((100, 100), 1)]);
+        assert_eq!(grid.dirty_chunks(), vec![(0, 0), (6, 6)]);
+    }
+
+    #[test]
+    fn undo_grid_test() {
+        let mut grid = UndoGrid::new(Grid::default() as Grid<u32, 16>, 2);
+        grid.insert((0, 0), 1);
+        grid.begin();
+        grid.insert((0, 0), 2);
+        grid.insert((0, 0), 3);
+        grid.insert((-20, 5), 4);
+        *grid.get_mut((-20, 5)).unwrap() += 1;
+        assert!(grid.in_transaction());
+        grid.commit();
+        assert_eq!(grid.get((0, 0)), Some(&3));
+        assert_eq!(grid.get((-20, 5)), Some(&5));
+
+        assert!(grid.undo());
+        assert_eq!(grid.get((0, 0)), Some(&1));
+        assert_eq!(grid.get((-20, 5)), None);
+        assert!(grid.redo());
+        assert_eq!(grid.get((0, 0)), Some(&3));
+        assert_eq!(grid.get((-20, 5)), Some(&5));
+        assert!(!grid.redo());
+
+        grid.begin();
+        grid.remove((0, 0));
+        grid.insert((1, 1), 6);
+        grid.rollback();
+        assert_eq!(grid.get((0, 0)), Some(&3));
+        assert_eq!(grid.get((1, 1)), None);
+        assert_eq!(grid.grid().len(), 2);
+
+        // only the last two transactions are kept
+        grid.remove((0, 0));
+        assert!(grid.undo());
+        assert!(grid.undo());
+        assert!(!grid.undo());
+        assert_eq!(grid.get((0, 0)), Some(&1));
+        assert!(grid.can_redo());
+        grid.insert((2, 2), 7);
+        assert!(!grid.can_redo());
+    }
+
+    #[test]
+    fn grid_snapshot_test() {
+        let mut grid = Grid::default() as Grid<u32, 16>;
+        for i in 0..64 {
+            grid.insert((i, i), i as u32);
+        }
+        let shared = |a:&Grid<u32, 16>, b:&Grid<u32, 16>| a.chunks.iter().filter(|(k, v)|b.chunks.get(k).is_some_and(|x|Arc::ptr_eq(v, x))).count();
+        let snapshot = grid.snapshot();
+        assert_eq!(shared(&grid, &snapshot), 4);
+
+        // reads and misses do not copy chunks
+        assert_eq!(grid.get_mut((1, 0)), None);
+        assert_eq!(grid.remove((1, 0)), None);
+        assert_eq!(shared(&grid, &snapshot), 4);
+
+        *grid.get_mut((0, 0)).unwrap() = 100;
+        grid.insert((20, 20), 200);
+        assert_eq!(shared(&grid, &snapshot), 2);
+        assert_eq!(snapshot.get((0, 0)), Some(&0));
+        assert_eq!(snapshot.get((20, 20)), Some(&20));
+        assert_eq!(grid.get((0, 0)), Some(&100));
+
+        let mut other = snapshot.clone();
+        other.iter_rect_mut((32, 32), (47, 47)).for_each(|(_, x)|*x += 1);
+        assert_eq!(shared(&other, &snapshot), 3);
+        assert_eq!(snapshot.get((40, 40)), Some(&40));
+        assert_eq!(other.get((40, 40)), Some(&41));
+        let owned:Vec<_> = other.into_iter().collect();
+        assert_eq!(owned.len(), 64);
+        assert_eq!(snapshot.len(), 64);
+    }
+
+    #[test]
+    fn grid_non_clone_test() {
+        struct Tile(Box<dyn Fn() -> bool>);
+        let mut grid = Grid::default() as Grid<Tile, 16>;
+        for y in 0..4 {
+            for x in 0..40 {
+                grid.insert((x, y), Tile(Box::new(move || x == 20 && y < 3)));
+            }
+        }
+        assert!(grid.get((20, 0)).is_some_and(|x|(x.0)()));
+        *grid.get_mut((20, 0)).unwrap() = Tile(Box::new(|| true));
+        let path = grid.astar((0, 0), (39, 0), |x|(x.cell.0)()).unwrap();
+        assert!(path.contains(&(20, 3)));
+
+        let mut hit = None;
+        grid.cast_ray((0.5, 0.5), (39.5, 0.5), |x|{
+            hit = Some(x.index);
+            !(x.cell.0)()
+        });
+        assert_eq!(hit, Some((20, 0)));
+
+        grid.entry((100, 100)).or_insert_with(|| Tile(Box::new(|| false)));
+        assert_eq!(grid.iter().count(), 161);
+        assert_eq!(grid.iter_mut().count(), 161);
+        assert!(grid.remove_chunk((6, 6)).is_some());
+        assert_eq!(grid.into_iter().filter(|(_, x)|(x.0)()).count(), 3);
+
+        // grids of types which are not `Clone` can be serialized and read back
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Handle(u32);
+        let grid:Grid<Handle, 16> = (0..20).map(|x|((x, -x), Handle(x as u32))).collect();
+        let copy:Grid<Handle, 16> = bincode::deserialize(&bincode::serialize(&grid).unwrap()).unwrap();
+        assert_eq!(copy.get((19, -19)), Some(&Handle(19)));
+        assert_eq!(copy.len(), 20);
+    }
+
+    #[test]
+    fn palette_chunk_test() {
+        let mut grid:Grid<u32, 16> = Grid::default();
+        assert!(!grid.compact_chunk((2, -1)));
+        for (i, x) in (32..48).enumerate() {
+            grid.insert((x, -16), 7 + (i % 2) as u32);
+        }
+        assert!(grid.compact_chunk((2, -1)));
+        let chunk = grid.chunk((2, -1)).unwrap();
+        assert!(chunk.is_palette());
+        assert_eq!((chunk.len(), chunk.palette_len()), (16, Some(3)));
+        assert_eq!(chunk.get_local(1), Some(&Some(8)));
+        assert_eq!(chunk.get_local(16), Some(&None));
+        assert_eq!(chunk.get_local(16 * 16), None);
+        assert_eq!(chunk.bounds(), Some(((32, -16), (47, -16))));
+
+        // cells are accessed as in flat chunks, reusing palette entries for equal values
+        grid.insert((32, -15), 8);
+        *grid.get_mut((33, -16)).unwrap() += 1;
+        assert_eq!(grid.remove((34, -16)), Some(7));
+        assert_eq!(grid.chunk((2, -1)).unwrap().palette_len(), Some(4));
+        assert_eq!(grid.iter_rect((32, -16), (35, -15)).map(|(_, x)|*x).collect::<Vec<_>>(), vec![7, 9, 8, 8]);
+        let mut cells:Vec<_> = grid.iter().map(|(index, x)|(index, *x)).collect();
+        cells.sort();
+        assert_eq!(cells.len(), 16);
+        assert_eq!(cells[0], ((32, -16), 7));
+
+        // the chunk is freed when the last cell is removed
+        grid.fill_chunk((0, 0), 1);
+        grid.insert((0, 0), 2);
+        assert!(grid.chunk((0, 0)).unwrap().is_palette());
+        for index in grid.iter_rect((0, 0), (15, 15)).map(|(index, _)|index).collect::<Vec<_>>() {
+            grid.remove(index);
+        }
+        assert!(!grid.contains_chunk((0, 0)));
+
+        // the bits per cell grow with the palette until the chunk is upgraded to a value per cell
+        for local in 0..254 {
+            grid.insert((local % 16, local / 16), local as u32);
+        }
+        assert!(grid.compact_chunk((0, 0)));
+        grid.insert((14, 15), 254);
+        assert!(grid.chunk((0, 0)).unwrap().is_palette());
+        grid.insert((15, 15), 1000);
+        let chunk = grid.chunk((0, 0)).unwrap();
+        assert!(!chunk.is_palette());
+        assert_eq!(chunk.len(), 256);
+        assert!((0..255).all(|local|chunk.get_local(local) == Some(&Some(local as u32))));
+        assert_eq!(chunk.get_local(255), Some(&Some(1000)));
+
+        // a full chunk with a single value becomes uniform
+        for local in 0..255 {
+            grid.insert((local % 16, local / 16), 1000);
+        }
+        assert_eq!(grid.compact(), 2);
+        assert!(grid.chunk((0, 0)).unwrap().is_uniform());
+
+        // palette chunks are stored as they are in the compact form and validated when read back
+        let Compact(copy):Compact<Grid<u32, 16>> = bincode::deserialize(&bincode::serialize(&Compact(&grid)).unwrap()).unwrap();
+        assert!(copy.chunk((2, -1)).unwrap().is_palette());
+        assert!(grid.iter().all(|(index, x)|copy.get(index) == Some(x)));
+        let chunk = copy.chunk((2, -1)).unwrap().clone();
+        let bytes = bincode::serialize(&Compact(&chunk)).unwrap();
+        assert!(bincode::deserialize::<Compact<Chunk<u32, 16>>>(&bytes).is_ok());
+        assert!(bincode::deserialize::<Compact<Chunk<u32, 16>>>(&bytes[..bytes.len() - 8]).is_err());
+        let corrupt = |f:fn(&mut Chunk<u32, 16>)| {
+            let mut chunk = chunk.clone();
+            f(&mut chunk);
+            bincode::deserialize::<Compact<Chunk<u32, 16>>>(&bincode::serialize(&Compact(&chunk)).unwrap()).is_err()
+        };
+        assert!(corrupt(|chunk|chunk.len += 1));
+        assert!(corrupt(|chunk|if let Storage::Palette(palette) = &mut chunk.storage { palette.bits = 0 }));
+        assert!(corrupt(|chunk|if let Storage::Palette(palette) = &mut chunk.storage { palette.words.pop(); }));
+        assert!(corrupt(|chunk|if let Storage::Palette(palette) = &mut chunk.storage { palette.words.fill(u64::MAX) }));
+
+        // compact chunks taken out of a grid keep the same API
+        let mut chunk = grid.remove_chunk((2, -1)).unwrap();
+        assert!(chunk.is_palette());
+        *chunk.get_local_mut(0).unwrap() += 10;
+        assert_eq!(chunk.remove(1), Some(9));
+        chunk.insert(2, 11);
+        assert_eq!(chunk.into_iter().take(3).collect::<Vec<_>>(), vec![((32, -16), 17), ((34, -16), 11), ((35, -16), 8)]);
+        let chunk = grid.remove_chunk((0, 0)).unwrap();
+        assert!(chunk.is_uniform());
+        assert_eq!(chunk.into_iter().count(), 256);
+    }
+
+    #[test]
+    fn uniform_chunk_test() {
+        let mut grid:Grid<u32, 16> = Grid::default();
+        assert!(grid.fill_chunk((0, 0), 5).is_none());
+        grid.fill_chunk((-1, 0), 6);
+        let chunk = grid.chunk((0, 0)).unwrap();
+        assert!(chunk.is_uniform());
+        assert_eq!((chunk.len(), chunk.bounds()), (256, Some(((0, 0), (15, 15)))));
+        assert_eq!(grid.get((15, 15)), Some(&5));
+        assert_eq!(grid.get((-1, 3)), Some(&6));
+        assert_eq!(grid.iter_rect((-2, 0), (1, 0)).map(|(_, x)|*x).collect::<Vec<_>>(), vec![6, 6, 5, 5]);
+        assert_eq!(grid.connected_components(Connectivity::Four, |x|*x == 5).components[0].size, 256);
+
+        // uniform chunks are stored as their single value in the compact form and expanded by the default form
+        let bytes = bincode::serialize(&Compact(&grid)).unwrap();
+        assert!(bytes.len() < 256);
+        let expanded:Grid<u32, 16> = bincode::deserialize(&bincode::serialize(&grid).unwrap()).unwrap();
+        assert!(!expanded.chunk((0, 0)).unwrap().is_uniform());
+        assert!(grid.iter().all(|(index, x)|expanded.get(index) == Some(x)));
+        let Compact(mut copy):Compact<Grid<u32, 16>> = bincode::deserialize(&bytes).unwrap();
+        assert!(copy.chunk((0, 0)).unwrap().is_uniform());
+        assert!(grid.iter().all(|(index, x)|copy.get(index) == Some(x)));
+        assert_eq!(copy.len(), grid.len());
+        copy.insert((0, 0), 1);
+        assert_eq!(copy.get((0, 0)), Some(&1));
+        let mut chunk = copy.remove_chunk((-1, 0)).unwrap();
+        assert!(chunk.is_uniform());
+        assert_eq!(chunk.remove(0), Some(6));
+
+        // uniform chunks can be mutated in any grid they are moved to
+        let mut other:Grid<u32, 16> = Grid::default();
+        other.insert_chunk(chunk);
+        assert_eq!(other.remove((-15, 0)), Some(6));
+        let mut replica:Grid<u32, 16> = Grid::default();
+        grid.take_changes().apply(&mut replica);
+        *replica.get_mut((1, 1)).unwrap() += 1;
+        assert_eq!((replica.get((1, 1)), replica.get((2, 1))), (Some(&6), Some(&5)));
+
+        // chunks are only expanded by iterating their cells mutably
+        assert!(grid.chunk_mut((0, 0)).unwrap().is_uniform());
+        assert!(grid.chunks_ordered_mut().all(|chunk|chunk.is_uniform()));
+        grid.values_mut().for_each(|_|{});
+        assert!(!grid.chunk((-1, 0)).unwrap().is_uniform());
+        assert_eq!(grid.compact(), 2);
+        assert!(grid.chunk((0, 0)).unwrap().is_uniform() && grid.chunk((-1, 0)).unwrap().is_uniform());
+
+        // the chunk is split into a palette chunk once a cell diverges
+        grid.insert((3, 4), 7);
+        let chunk = grid.chunk((0, 0)).unwrap();
+        assert!(!chunk.is_uniform() && chunk.is_palette());
+        assert_eq!(chunk.len(), 256);
+        assert_eq!((grid.get((3, 4)), grid.get((4, 4))), (Some(&7), Some(&5)));
+        assert_eq!(grid.remove((-16, 0)), Some(6));
+        assert_eq!(grid.chunk((-1, 0)).unwrap().len(), 255);
+
+        // chunks filled with comparable values reuse the palette entry of values written again
+        assert!(grid.fill_chunk_eq((0, 0), 7).is_some());
+        for index in 0..256 {
+            grid.insert((index % 16, index / 16), 7);
+        }
+        let chunk = grid.chunk((0, 0)).unwrap();
+        assert!(chunk.is_palette());
+        assert_eq!((chunk.palette_len(), chunk.len()), (Some(2), 256));
+        grid.fill_chunk((0, 0), 7);
+        for index in 0..256 {
+            grid.insert((index % 16, index / 16), 7);
+        }
+        assert!(!grid.chunk((0, 0)).unwrap().is_palette());
+    }
+
+    #[test]
+    fn grid_bounds_test() {
+        let mut grid = Grid::default() as Grid<u32, 16>;
+        assert_eq!(grid.bounds(), None);
+        assert_eq!(grid.chunk_bounds(), None);
+        grid.insert((3, 4), 0);
+        assert_eq!(grid.bounds(), Some(((3, 4), (3, 4))));
+        grid.insert((-20, 5), 1);
+        grid.insert((40, -1), 2);
+        grid.insert((10, 10), 3);
+        assert_eq!(grid.bounds(), Some(((-20, -1), (40, 10))));
+        assert_eq!(grid.chunk_bounds(), Some(((-2, -1), (2, 0))));
+
+        grid.remove((40, -1));
+        assert_eq!(grid.bounds(), Some(((-20, 4), (10, 10))));
+        assert_eq!(grid.chunk_bounds(), Some(((-2, 0), (0, 0))));
+        grid.remove((10, 10));
+        assert!(grid.chunk((0, 0)).unwrap().bounds.get().is_none());
+        assert_eq!(grid.bounds(), Some(((-20, 4), (3, 5))));
+        assert!(grid.chunk((0, 0)).unwrap().bounds.get().is_some());
+        grid.remove((3, 4));
+        grid.remove((-20, 5));
+        assert_eq!(grid.bounds(), None);
+
+        // bounds are not serialized and get recomputed
+        let grid:Grid<u32, 16> = [((0, 2), 0), ((20, 0), 1), ((5, 1), 2)].into_iter().collect();
+        let copy:Grid<u32, 16> = bincode::deserialize(&bincode::serialize(&grid).unwrap()).unwrap();
+        assert_eq!(copy.bounds(), Some(((0, 0), (20, 2))));
+        assert!(copy.chunk((1, 0)).unwrap().bounds.get().is_some());
+    }
+
+    #[test]
+    fn flood_fill_test() {
+        let mut grid = Grid::default() as Grid<bool>;
+        for y in -20..20 {
+            for x in -20..20 {
+                grid.insert((x, y), (x * 31 + y * 17 + x * y).rem_euclid(5) < 2);
+            }
+        }
+        // a diagonal staircase which is only connected with eight-connectivity
+        for i in 30..40 {
+            grid.insert((i, i), true);
+        }
+
+        let filled = grid.flood_fill((30, 30), Connectivity::Four, |x| *x);
+        assert_eq!(filled.len(), 1);
+        let filled = grid.flood_fill((30, 30), Connectivity::Eight, |x| *x);
+        assert_eq!(filled.len(), 10);
+        assert_eq!(grid.flood_fill((100, 100), Connectivity::Eight, |x| *x).len(), 0);
+
+        for connectivity in [Connectivity::Four, Connectivity::Eight] {
+            let components = grid.connected_components(connectivity, |x| *x);
+            assert_eq!(components.labels.len(), grid.values().filter(|x| **x).count());
+            assert_eq!(components.components.iter().map(|x| x.size).sum::<usize>(), components.labels.len());
+            for (index, label) in components.labels.iter() {
+                let filled = grid.flood_fill(index, connectivity, |x| *x);
+                let component = components.components[*label as usize];
+                assert_eq!(component.size, filled.len());
+                assert!(filled.keys().all(|p| components.labels.get(p) == Some(label)));
+                let min = filled.keys().fold(index, |a, b| (a.0.min(b.0), a.1.min(b.1)));
+                let max = filled.keys().fold(index, |a, b| (a.0.max(b.0), a.1.max(b.1)));
+                assert_eq!((component.min, component.max), (min, max));
+            }
+        }
+        let components = grid.connected_components(Connectivity::Eight, |x| *x);
+        let label = components.labels.get((30, 30)).unwrap();
+        assert_eq!(components.components[*label as usize], Component { size:10, min:(30, 30), max:(39, 39) });
+    }
+
     #[test]
     fn astar_test() {
         let mut grid = Grid::default() as Grid<bool>;
@@ -623,6 +2387,6 @@ mod tests {
         let path = path.unwrap();
         assert_eq!(*path.last().unwrap(), (7,0));
         assert_eq!(*path.first().unwrap(), (0,0));
-        assert!(path.iter().any(|x| *x == (4,7)));
+        assert!(path.contains(&(4,7)));
     }
 }
@@ -0,0 +1,232 @@
+use std::{collections::HashMap, hash::Hash};
+use serde::{Deserialize, Serialize};
+use crate::CellFns;
+
+/// Palettes needing more bits per cell than this are upgraded to a value per cell
+pub(crate) const MAX_PALETTE_BITS:u32 = 8;
+
+/// Cells of a palette `Chunk`, i.e. its distinct values and a bit-packed index into them per cell
+///
+/// Every entry counts the cells using it, entries no longer used are reused by the next new value
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de> + Clone"))]
+pub(crate) struct Palette<T> {
+    /// Values and the number of cells using them, entry `0` is the empty cell and is always `None`
+    entries:Vec<(Option<T>, usize)>,
+    /// Bits per cell in `words`
+    pub(crate) bits:u32,
+    pub(crate) words:Vec<u64>,
+    /// Clones values shared by several cells, deserialized palettes do not compare values and give new values their own entry
+    #[serde(skip, default = "CellFns::cloning")]
+    fns:CellFns<T>,
+}
+
+/// Gets the number of bits needed to store the palette indices `0..=max`
+fn bits_for(max:usize) -> u32 {
+    (usize::BITS - max.leading_zeros()).max(1)
+}
+
+impl<T> Palette<T> {
+    /// Creates a palette with every cell holding `value`
+    pub(crate) fn filled<const N: usize>(value:Option<T>, fns:CellFns<T>) -> Self {
+        let mut palette = Self::with_entries::<N>(vec![(None, 0), (value, N * N)], fns);
+        for local in 0..N * N {
+            palette.write(local, 1);
+        }
+        palette
+    }
+
+    fn with_entries<const N: usize>(entries:Vec<(Option<T>, usize)>, fns:CellFns<T>) -> Self {
+        let bits = bits_for(entries.len() - 1);
+        Self { entries, bits, words:vec![0; (N * N).div_ceil((64 / bits) as usize)], fns }
+    }
+
+    /// Gets the number of entries, including the empty cell and entries no longer used by any cell
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn read(&self, local:usize) -> usize {
+        let per_word = (64 / self.bits) as usize;
+        let shift = (local % per_word) as u32 * self.bits;
+        ((self.words[local / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+    }
+
+    fn write(&mut self, local:usize, i:usize) {
+        let per_word = (64 / self.bits) as usize;
+        let shift = (local % per_word) as u32 * self.bits;
+        let word = &mut self.words[local / per_word];
+        *word = (*word & !(((1 << self.bits) - 1) << shift)) | ((i as u64) << shift);
+    }
+
+    /// Gets the cell at `local`, which must be within the chunk
+    pub(crate) fn get(&self, local:usize) -> &Option<T> {
+        &self.entries[self.read(local)].0
+    }
+
+    /// Gets the cell at `local` mutably, which must be within the chunk and not be shared with other cells, see `unshare`
+    pub(crate) fn get_mut(&mut self, local:usize) -> Option<&mut T> {
+        let i = self.read(local);
+        self.entries[i].0.as_mut()
+    }
+
+    /// Adds a cell holding `t`, returning its entry or `t` if the palette is full
+    fn add<const N: usize>(&mut self, t:T, eq:bool) -> Result<usize, T> {
+        let existing = self.fns.eq.filter(|_|eq).and_then(|eq|self.entries.iter().skip(1).position(|(x, _)|x.as_ref().is_some_and(|x|eq(x, &t))));
+        let i = match existing {
+            Some(i) => i + 1,
+            None => match self.entries.iter().skip(1).position(|(_, count)|*count == 0) {
+                Some(i) => {
+                    self.entries[i + 1].0 = Some(t);
+                    i + 1
+                },
+                None if bits_for(self.entries.len()) > MAX_PALETTE_BITS => return Err(t),
+                None => {
+                    self.entries.push((Some(t), 0));
+                    self.repack::<N>(bits_for(self.entries.len() - 1));
+                    self.entries.len() - 1
+                },
+            },
+        };
+        self.entries[i].1 += 1;
+        Ok(i)
+    }
+
+    fn repack<const N: usize>(&mut self, bits:u32) {
+        if bits == self.bits {
+            return;
+        }
+        let mut repacked = Self { entries:Vec::new(), bits, words:vec![0; (N * N).div_ceil((64 / bits) as usize)], fns:self.fns };
+        for local in 0..N * N {
+            repacked.write(local, self.read(local));
+        }
+        (self.bits, self.words) = (repacked.bits, repacked.words);
+    }
+
+    /// Stops using the entry of the cell at `local`, dropping its value once no cell uses it
+    fn release(&mut self, local:usize) -> usize {
+        let i = self.read(local);
+        if i > 0 {
+            self.entries[i].1 -= 1;
+            self.write(local, 0);
+        }
+        i
+    }
+
+    /// Sets the cell at `local` to `t`, returning `t` if the palette is full
+    pub(crate) fn insert<const N: usize>(&mut self, local:usize, t:T) -> Result<(), T> {
+        let old = self.release(local);
+        match self.add::<N>(t, true) {
+            Ok(i) => {
+                if old > 0 && self.entries[old].1 == 0 && old != i {
+                    self.entries[old].0 = None;
+                }
+                self.write(local, i);
+                Ok(())
+            },
+            Err(t) => {
+                if old > 0 {
+                    self.entries[old].1 += 1;
+                    self.write(local, old);
+                }
+                Err(t)
+            },
+        }
+    }
+
+    /// Gives the cell at `local` its own entry such that it can be mutated, returning `false` if the palette is full
+    pub(crate) fn unshare<const N: usize>(&mut self, local:usize) -> bool {
+        let i = self.read(local);
+        if self.entries[i].1 <= 1 {
+            return true;
+        }
+        let Some(value) = self.entries[i].0.as_ref() else {
+            return true;
+        };
+        match self.add::<N>((self.fns.clone)(value), false) {
+            Ok(j) => {
+                self.entries[i].1 -= 1;
+                self.write(local, j);
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// Removes the cell at `local`, cloning its value if it is shared with other cells
+    pub(crate) fn remove(&mut self, local:usize) -> Option<T> {
+        let i = self.release(local);
+        if i == 0 {
+            return None;
+        }
+        if self.entries[i].1 == 0 {
+            return self.entries[i].0.take();
+        }
+        self.entries[i].0.as_ref().map(self.fns.clone)
+    }
+
+    /// Converts the palette into a value per cell, moving the value of each entry into its last cell
+    pub(crate) fn expand<const N: usize>(mut self) -> Vec<Option<T>> {
+        let clone = self.fns.clone;
+        (0..N * N).map(|local|{
+            let i = self.read(local);
+            let (value, count) = &mut self.entries[i];
+            if i == 0 {
+                return None;
+            }
+            *count -= 1;
+            if *count == 0 { value.take() } else { value.as_ref().map(clone) }
+        }).collect()
+    }
+
+    /// Copies the palette using its clone function for every value
+    pub(crate) fn duplicate(&self) -> Self {
+        let entries = self.entries.iter().map(|(value, count)|(value.as_ref().map(self.fns.clone), *count)).collect();
+        Self { entries, bits:self.bits, words:self.words.clone(), fns:self.fns }
+    }
+
+    /// Returns `true` if the deserialized palette is consistent and holds `len` cells
+    pub(crate) fn is_valid<const N: usize>(&self, len:usize) -> bool {
+        if !(1..=MAX_PALETTE_BITS).contains(&self.bits)
+            || self.words.len() != (N * N).div_ceil((64 / self.bits) as usize)
+            || self.entries.len() > 1 << self.bits
+            || self.entries.first().is_none_or(|(value, _)|value.is_some()) {
+            return false;
+        }
+        let mut counts = vec![0; self.entries.len()];
+        for local in 0..N * N {
+            match counts.get_mut(self.read(local)) {
+                Some(count) => *count += 1,
+                None => return false,
+            }
+        }
+        len == N * N - counts[0] && self.entries.iter().zip(counts).skip(1).all(|((value, count), n)|*count == n && (n == 0 || value.is_some()))
+    }
+}
+
+impl<T:Eq + Hash + Clone> Palette<T> {
+    /// Creates a palette from a value per cell, or returns `None` if the cells hold more distinct values than a palette can
+    pub(crate) fn from_cells<const N: usize>(cells:&[Option<T>]) -> Option<Self> {
+        let mut indices:HashMap<&T, usize> = HashMap::new();
+        let mut entries = vec![(None, 0)];
+        let cells:Vec<usize> = cells.iter().map(|cell|{
+            let Some(t) = cell else {
+                return 0;
+            };
+            let i = *indices.entry(t).or_insert_with(|| {
+                entries.push((Some(t.clone()), 0));
+                entries.len() - 1
+            });
+            entries[i].1 += 1;
+            i
+        }).collect();
+        if bits_for(entries.len() - 1) > MAX_PALETTE_BITS {
+            return None;
+        }
+        let mut palette = Self::with_entries::<N>(entries, CellFns::comparing());
+        for (local, i) in cells.into_iter().enumerate() {
+            palette.write(local, i);
+        }
+        Some(palette)
+    }
+}
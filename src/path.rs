@@ -0,0 +1,202 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+use crate::{AStarVisit, Entry, Grid};
+
+/// How diagonal moves are treated when passing by blocked cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CornerCutting {
+    /// Diagonal moves are always allowed
+    Allow,
+
+    /// Diagonal moves are allowed if at least one of the two adjacent orthogonal cells is passable
+    IfOneOpen,
+
+    /// Diagonal moves are only allowed if both adjacent orthogonal cells are passable
+    #[default]
+    Never,
+}
+
+/// Neighbours expanded by `Grid::astar_with` and `Grid::dijkstra_map`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Movement {
+    /// The four orthogonal neighbours
+    #[default]
+    Orthogonal,
+
+    /// The four orthogonal and the four diagonal neighbours
+    Diagonal(CornerCutting),
+}
+
+/// Heuristic used by `Grid::astar_with` to estimate the remaining cost to the goal
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Heuristic {
+    /// Sum of the horizontal and vertical distance, suited for `Movement::Orthogonal`
+    #[default]
+    Manhattan,
+
+    /// Distance when moving diagonally as much as possible, suited for `Movement::Diagonal`
+    Octile,
+
+    /// Straight line distance, scaled such that it never exceeds the cost of orthogonal or diagonal steps
+    Euclidean,
+
+    /// No estimate, which turns the search into Dijkstra's algorithm
+    Zero,
+}
+
+/// Options of `Grid::astar_with`
+///
+/// `orthogonal_cost` and `diagonal_cost` are the lowest cost of an orthogonal and a diagonal step
+/// and are used by the heuristic, i.e. they must not exceed what the cost function returns for the path to be optimal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AStarOptions {
+    pub movement:Movement,
+    pub heuristic:Heuristic,
+    pub orthogonal_cost:u32,
+    pub diagonal_cost:u32,
+}
+
+impl Default for AStarOptions {
+    fn default() -> Self {
+        Self { movement:Movement::Orthogonal, heuristic:Heuristic::Manhattan, orthogonal_cost:1, diagonal_cost:1 }
+    }
+}
+
+impl AStarOptions {
+    /// Options for 8-directional movement with an octile heuristic, where orthogonal steps cost at least 10 and diagonal steps at least 14
+    pub fn diagonal(corner_cutting:CornerCutting) -> Self {
+        Self { movement:Movement::Diagonal(corner_cutting), heuristic:Heuristic::Octile, orthogonal_cost:10, diagonal_cost:14 }
+    }
+
+    /// Estimates the cost of going from `from` to `to`
+    pub fn estimate(&self, from:(i32, i32), to:(i32, i32)) -> u32 {
+        let dx = from.0.abs_diff(to.0);
+        let dy = from.1.abs_diff(to.1);
+        match self.heuristic {
+            Heuristic::Manhattan => dx.saturating_add(dy).saturating_mul(self.orthogonal_cost),
+            Heuristic::Octile => {
+                let (min, max) = (dx.min(dy), dx.max(dy));
+                let diagonal_cost = self.diagonal_cost.min(self.orthogonal_cost.saturating_mul(2));
+                (max - min).saturating_mul(self.orthogonal_cost).saturating_add(min.saturating_mul(diagonal_cost))
+            },
+            Heuristic::Euclidean => {
+                let scale = (self.orthogonal_cost as f64).min(self.diagonal_cost as f64 / std::f64::consts::SQRT_2);
+                ((dx as f64).hypot(dy as f64) * scale) as u32
+            },
+            Heuristic::Zero => 0,
+        }
+    }
+}
+
+const ORTHOGONAL:[(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL:[(i32, i32); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Expands the neighbours of `from` according to `movement`, calling `cost` for each neighbour present in the grid
+    ///
+    /// Returns the passable neighbours together with the cost of stepping into them,
+    /// neighbours outside of the range covered by `i32` indices are skipped
+    pub(crate) fn neighbours<F:Fn(AStarVisit<T>)->Option<u32>>(&self, from:(i32, i32), movement:Movement, cost:&F) -> Vec<((i32, i32), u32)> {
+        let step = |(dx, dy):(i32, i32)| {
+            let index = (from.0.checked_add(dx)?, from.1.checked_add(dy)?);
+            let cell = self.get(index)?;
+            Some((index, cost(AStarVisit { index, cell, from })?))
+        };
+        let orthogonal = ORTHOGONAL.map(step);
+        let mut vec:Vec<((i32, i32), u32)> = Vec::with_capacity(8);
+        vec.extend(orthogonal.into_iter().flatten());
+        let Movement::Diagonal(corner_cutting) = movement else {
+            return vec;
+        };
+        for (dx, dy) in DIAGONAL {
+            let horizontal = orthogonal[if dx < 0 { 0 } else { 1 }].is_some();
+            let vertical = orthogonal[if dy < 0 { 2 } else { 3 }].is_some();
+            let allowed = match corner_cutting {
+                CornerCutting::Allow => true,
+                CornerCutting::IfOneOpen => horizontal || vertical,
+                CornerCutting::Never => horizontal && vertical,
+            };
+            if !allowed {
+                continue;
+            }
+            vec.extend(step((dx, dy)));
+        }
+        vec
+    }
+
+    /// Perform the A-star algorithm with configurable movement and per step costs
+    ///
+    /// `F` returns the cost of stepping from `AStarVisit::from` into the visited cell or `None` if the step is blocked.
+    /// Cells not present in the grid are always blocked.
+    ///
+    /// Returns the path from `start` to `end` (both inclusive) together with its total cost
+    pub fn astar_with<F:Fn(AStarVisit<T>)->Option<u32>>(&self, start:impl Into<(i32, i32)>, end:impl Into<(i32, i32)>, options:AStarOptions, cost:F) -> Option<(Vec<(i32, i32)>, u32)> {
+        let start = start.into();
+        let end = end.into();
+        pathfinding::directed::astar::astar(&start, |n| {
+            self.neighbours(*n, options.movement, &cost)
+        }, |n|{
+            options.estimate(*n, end)
+        }, |n|{
+            n == &end
+        })
+    }
+
+    /// Computes a Dijkstra map, i.e. the lowest cost of reaching each cell from any of the `sources`
+    ///
+    /// Neighbours are expanded as by `astar_with` using `movement` and `cost`.
+    /// Cells which cannot be reached or would cost more than `max_cost` are not part of the returned map.
+    pub fn dijkstra_map<F:Fn(AStarVisit<T>)->Option<u32>>(&self, sources:impl IntoIterator<Item = (i32, i32)>, max_cost:u32, movement:Movement, cost:F) -> Grid<u32, N> {
+        let mut map = Grid::default() as Grid<u32, N>;
+        let mut heap = BinaryHeap::new();
+        for source in sources {
+            map.insert(source, 0);
+            heap.push(Reverse((0, source)));
+        }
+        while let Some(Reverse((c, index))) = heap.pop() {
+            if map.get(index).is_some_and(|x|*x < c) {
+                continue;
+            }
+            for (neighbour, step) in self.neighbours(index, movement, &cost) {
+                let c = c.saturating_add(step);
+                if c > max_cost {
+                    continue;
+                }
+                match map.entry(neighbour) {
+                    Entry::Occupied(mut e) if *e.get() > c => { e.insert(c); },
+                    Entry::Occupied(_) => continue,
+                    Entry::Vacant(e) => { e.insert(c); },
+                }
+                heap.push(Reverse((c, neighbour)));
+            }
+        }
+        map
+    }
+}
+
+impl<const N: usize> Grid<u32, N> {
+    /// Gets the neighbour of `from` with the lowest value in a Dijkstra map, if it is lower than the value of `from`
+    pub fn downhill(&self, from:impl Into<(i32, i32)>, movement:Movement) -> Option<(i32, i32)> {
+        let from = from.into();
+        let current = *self.get(from)?;
+        let (index, value) = self.neighbours(from, movement, &|x|Some(*x.cell))
+            .into_iter()
+            .min_by_key(|(_, value)|*value)?;
+        (value < current).then_some(index)
+    }
+
+    /// Walks downhill in a Dijkstra map from `from` until a local minimum such as a source is reached
+    ///
+    /// Returns the visited cells including `from`, or an empty path if `from` is not part of the map
+    pub fn walk_downhill(&self, from:impl Into<(i32, i32)>, movement:Movement) -> Vec<(i32, i32)> {
+        let mut index = from.into();
+        if self.get(index).is_none() {
+            return Vec::new();
+        }
+        let mut path = vec![index];
+        while let Some(next) = self.downhill(index, movement) {
+            path.push(next);
+            index = next;
+        }
+        path
+    }
+}
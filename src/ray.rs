@@ -0,0 +1,300 @@
+use glam::Vec2;
+use crate::{Grid, Index};
+
+/// Struct used by the `Grid::cast_ray`
+pub struct RayVisit<'a, T> {
+    /// Current index of the cell being visited
+    pub index:(i32, i32),
+
+    /// The cell being visited
+    pub cell:&'a T,
+
+    /// Current position of the ray in whole cells, i.e. the index of the cell as floats
+    pub pos:(f32, f32),
+
+    /// Distance traveled by the ray when entering the cell
+    pub d:f32,
+
+    /// Distance traveled by the ray when leaving the cell, or the length of the ray if it ends within the cell
+    pub d_exit:f32,
+
+    /// Exact point where the ray entered the cell
+    pub point:(f32, f32),
+
+    /// Normal of the face through which the ray entered the cell, `(0, 0)` for the cell containing `start`
+    pub normal:(i32, i32),
+}
+
+/// Struct used by the `Grid::cast_ray_sparse`
+/// 
+/// Equal to `RayVisit` except that the cell is `None` if it is not present in the grid
+pub struct SparseRayVisit<'a, T> {
+    /// Current index of the cell being visited, or of the first cell visited in an empty chunk
+    pub index:(i32, i32),
+
+    /// The cell being visited, `None` if the cell is not present in the grid
+    pub cell:Option<&'a T>,
+
+    /// Current position of the ray in whole cells, i.e. the index of the cell as floats
+    pub pos:(f32, f32),
+
+    /// Distance traveled by the ray when entering the cell
+    pub d:f32,
+
+    /// Distance traveled by the ray when leaving the cell or empty chunk, or the length of the ray if it ends within
+    pub d_exit:f32,
+
+    /// Exact point where the ray entered the cell
+    pub point:(f32, f32),
+
+    /// Normal of the face through which the ray entered the cell, `(0, 0)` for the cell containing `start`
+    pub normal:(i32, i32),
+}
+
+/// First cell hit by a ray, returned by `Grid::raycast_first`
+pub struct RayHit<'a, T> {
+    /// Index of the cell which was hit
+    pub index:(i32, i32),
+
+    /// The cell which was hit
+    pub cell:&'a T,
+
+    /// Exact point where the ray hit the cell
+    pub point:(f32, f32),
+
+    /// Normal of the face which was hit, `(0, 0)` if the ray started within the cell
+    pub normal:(i32, i32),
+
+    /// Distance traveled by the ray until the cell was hit
+    pub d:f32,
+}
+
+/// A cell traversed by a `Ray`
+pub(crate) struct RayStep {
+    pub index:(i32, i32),
+    pub pos:(f32, f32),
+    pub d:f32,
+    pub d_exit:f32,
+    pub point:(f32, f32),
+    pub normal:(i32, i32),
+}
+
+impl RayStep {
+    pub fn visit<T>(self, cell:&T) -> RayVisit<'_, T> {
+        RayVisit { index:self.index, cell, pos:self.pos, d:self.d, d_exit:self.d_exit, point:self.point, normal:self.normal }
+    }
+
+    pub fn sparse_visit<T>(self, cell:Option<&T>) -> SparseRayVisit<'_, T> {
+        SparseRayVisit { index:self.index, cell, pos:self.pos, d:self.d, d_exit:self.d_exit, point:self.point, normal:self.normal }
+    }
+}
+
+/// Traverses the cells intersected by a ray from `start` to `end`, ordered by their distance from `start`
+pub(crate) struct Ray {
+    start:Vec2,
+    dir:Vec2,
+    length:f32,
+    tile_x:f32,
+    tile_y:f32,
+    dtile_x:f32,
+    dtile_y:f32,
+    dt_x:f32,
+    dt_y:f32,
+    ddt_x:f32,
+    ddt_y:f32,
+    t:f32,
+    normal:(i32, i32),
+}
+
+impl Ray {
+    pub fn new(start:impl Into<(f32, f32)>, end:impl Into<(f32, f32)>) -> Option<Self> {
+        let start:(f32, f32) = start.into();
+        let end:(f32, f32) = end.into();
+        let start:Vec2 = start.into();
+        let end:Vec2 = end.into();
+        let v = end - start;
+        let dir = v.normalize_or_zero();
+        if dir.length() == 0.0 {
+            return None;
+        }
+        let mut ray = Self {
+            start,
+            dir,
+            length:v.length(),
+            tile_x:0.0,
+            tile_y:0.0,
+            dtile_x:0.0,
+            dtile_y:0.0,
+            dt_x:0.0,
+            dt_y:0.0,
+            ddt_x:0.0,
+            ddt_y:0.0,
+            t:0.0,
+            normal:(0, 0),
+        };
+        ray.start_at(0.0);
+        Some(ray)
+    }
+
+    /// Restarts the traversal at distance `t` from `start`
+    fn start_at(&mut self, t:f32) {
+        fn get_helper(cell_size:f32, pos:f32, dir:f32) -> (f32, f32, f32, f32) {
+            let tile = (pos / cell_size).floor();// + 1.0;
+            let dtile;
+            let dt;
+            let mut dir = dir;
+            if dir == 0.0 {
+                dir = 0.00001; // FIXME: avoid divide by zero but can be solved better
+            }
+            if dir > 0.0 {
+                dtile = 1.0;
+                dt = ((tile + 1.0) * cell_size - pos) / dir;
+            } else {
+                dtile = -1.0;
+                dt = (tile  * cell_size - pos) / dir;
+            }
+
+            (tile, dtile, dt, dtile * cell_size / dir)
+        }
+        let pos = self.start + self.dir * t;
+        (self.tile_x, self.dtile_x, self.dt_x, self.ddt_x) = get_helper(1.0, pos.x, self.dir.x);
+        (self.tile_y, self.dtile_y, self.dt_y, self.ddt_y) = get_helper(1.0, pos.y, self.dir.y);
+        self.t = t;
+    }
+}
+
+impl Ray {
+    /// Gets the cell the ray is currently in, or `None` if the ray has ended
+    pub fn current(&self) -> Option<RayStep> {
+        if self.length < self.t {
+            return None;
+        }
+        let point = self.start + self.dir * self.t;
+        Some(RayStep {
+            index:(self.tile_x as i32, self.tile_y as i32),
+            pos:(self.tile_x, self.tile_y),
+            d:self.t,
+            d_exit:(self.t + self.dt_x.min(self.dt_y)).min(self.length),
+            point:point.into(),
+            normal:self.normal,
+        })
+    }
+
+    /// Advances the ray out of the rectangle spanned by `min` and `max` (both inclusive), which must contain the current cell
+    ///
+    /// This is equal to advancing the ray cell by cell until it leaves the rectangle, but takes constant time
+    pub fn skip_rect(&mut self, min:(i32, i32), max:(i32, i32)) {
+        let nx = if self.dtile_x > 0.0 { max.0 as f32 - self.tile_x } else { self.tile_x - min.0 as f32 };
+        let ny = if self.dtile_y > 0.0 { max.1 as f32 - self.tile_y } else { self.tile_y - min.1 as f32 };
+        let exit_x = self.dt_x + nx * self.ddt_x;
+        let exit_y = self.dt_y + ny * self.ddt_y;
+        // number of boundaries crossed along the other axis before `r`
+        let crossed = |r:f32, dt:f32, ddt:f32, n:f32| if r > dt { (((r - dt) / ddt).floor() + 1.0).min(n) } else { 0.0 };
+        if exit_x < exit_y {
+            let r = exit_x;
+            let ky = crossed(r, self.dt_y, self.ddt_y, ny);
+            self.tile_x += self.dtile_x * (nx + 1.0);
+            self.tile_y += self.dtile_y * ky;
+            self.dt_x = self.ddt_x;
+            self.dt_y = self.dt_y + ky * self.ddt_y - r;
+            self.normal = (-self.dtile_x as i32, 0);
+            self.t += r;
+        } else {
+            let r = exit_y;
+            let kx = crossed(r, self.dt_x, self.ddt_x, nx);
+            self.tile_x += self.dtile_x * kx;
+            self.tile_y += self.dtile_y * (ny + 1.0);
+            self.dt_x = self.dt_x + kx * self.ddt_x - r;
+            self.dt_y = self.ddt_y;
+            self.normal = (0, -self.dtile_y as i32);
+            self.t += r;
+        }
+    }
+}
+
+impl Iterator for Ray {
+    type Item = RayStep;
+    fn next(&mut self) -> Option<Self::Item> {
+        let step = self.current()?;
+        if self.dt_x < self.dt_y {
+            self.tile_x += self.dtile_x;
+            self.normal = (-self.dtile_x as i32, 0);
+            let dt = self.dt_x;
+            self.t += dt;
+            self.dt_x = self.dt_x + self.ddt_x - dt;
+            self.dt_y -= dt;
+        } else {
+            self.tile_y += self.dtile_y;
+            self.normal = (0, -self.dtile_y as i32);
+            let dt = self.dt_y;
+            self.t += dt;
+            self.dt_x -= dt;
+            self.dt_y = self.dt_y + self.ddt_y - dt;
+        }
+        Some(step)
+    }
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Casts a ray from `start` to `end` and call a function `F` for each cell visited
+    ///
+    /// The ray will be traced until `F` returns `false`, untill `end` has been reached or until a cell is not present in the grid
+    pub fn cast_ray<F:FnMut(RayVisit<T>)->bool>(&self, start:impl Into<(f32, f32)>, end:impl Into<(f32, f32)>, mut f:F) {
+        let Some(ray) = Ray::new(start, end) else {
+            return;
+        };
+        for step in ray {
+            let Some(cell) = self.get(step.index) else {
+                break;
+            };
+            if !f(step.visit(cell)) {
+                break;
+            }
+        }
+    }
+
+    /// Casts a ray from `start` to `end` and returns the first cell for which `predicate` returns `true`
+    ///
+    /// Returns `None` if no such cell is found before `end` or before a cell is not present in the grid
+    pub fn raycast_first<F:FnMut(&T)->bool>(&self, start:impl Into<(f32, f32)>, end:impl Into<(f32, f32)>, mut predicate:F) -> Option<RayHit<'_, T>> {
+        for step in Ray::new(start, end)? {
+            let cell = self.get(step.index)?;
+            if predicate(cell) {
+                return Some(RayHit { index:step.index, cell, point:step.point, normal:step.normal, d:step.d });
+            }
+        }
+        None
+    }
+
+    /// Casts a ray from `start` to `end` and call a function `F` for each cell visited, including cells not present in the grid
+    ///
+    /// Unlike `cast_ray` the ray does not stop at missing cells, which are visited with `SparseRayVisit::cell` set to `None`.
+    /// Chunks not present in the grid are skipped entirely, i.e. `F` is only called once for the first cell of the
+    /// ray within such a chunk with `SparseRayVisit::d_exit` set to where the ray leaves the chunk.
+    ///
+    /// The ray will be traced until `F` returns `false` or untill `end` has been reached
+    pub fn cast_ray_sparse<F:FnMut(SparseRayVisit<T>)->bool>(&self, start:impl Into<(f32, f32)>, end:impl Into<(f32, f32)>, mut f:F) {
+        let Some(mut ray) = Ray::new(start, end) else {
+            return;
+        };
+        while let Some(mut step) = ray.current() {
+            let index = Index::from(step.index);
+            let chunk_index = index.chunk_index::<N>();
+            let cell = match self.chunks.get(&chunk_index) {
+                Some(chunk) => {
+                    ray.next();
+                    chunk.get_local(index.local_index::<N>()).and_then(|x|x.as_ref())
+                },
+                None => {
+                    let min:(i32, i32) = chunk_index.index::<N>().into();
+                    ray.skip_rect(min, (min.0 + N as i32 - 1, min.1 + N as i32 - 1));
+                    step.d_exit = ray.t.min(ray.length);
+                    None
+                },
+            };
+            if !f(step.sparse_visit(cell)) {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::ops::Range;
+use crate::{make_flat, Chunk, ChunkIndex, Grid, Index, Palette, Storage, CHUNK_SIZE};
+
+/// A chunk overlapping the rectangle of a `RectIter`, holding the cells which has not been visited yet
+struct RectChunk<S> {
+    left:i64,
+    offset:usize,
+    rest:S,
+}
+
+/// A row of chunks overlapping the rectangle of a `RectIter`, sorted by their left edge
+struct RectRow<S> {
+    top:i64,
+    chunks:Vec<RectChunk<S>>,
+}
+
+/// Cells of a chunk overlapping the rectangle of a `RectIter`
+enum Cells<'a, T> {
+    Slice(&'a [Option<T>]),
+    /// `len` cells of a uniform chunk, see `Chunk::is_uniform`
+    Uniform(&'a Option<T>, usize),
+    /// Cells of a palette chunk within the local range, see `Chunk::is_palette`
+    Palette(&'a Palette<T>, Range<usize>),
+}
+
+/// Iterator over `Cells`
+enum CellsIter<'a, T> {
+    Slice(std::slice::Iter<'a, Option<T>>),
+    Uniform(std::iter::RepeatN<&'a Option<T>>),
+    Palette(&'a Palette<T>, Range<usize>),
+}
+
+impl<T> Default for Cells<'_, T> {
+    fn default() -> Self {
+        Cells::Slice(&[])
+    }
+}
+
+impl<'a, T> Cells<'a, T> {
+    fn of<const N: usize>(chunk:&'a Chunk<T, N>) -> Self {
+        match &chunk.storage {
+            Storage::Flat => Cells::Slice(chunk.inner.as_slice()),
+            Storage::Uniform(value, _) => Cells::Uniform(value, N * N),
+            Storage::Palette(palette) => Cells::Palette(palette, 0..N * N),
+        }
+    }
+
+    fn split_at(self, n:usize) -> (Self, Self) {
+        match self {
+            Cells::Slice(cells) => {
+                let (a, b) = cells.split_at(n);
+                (Cells::Slice(a), Cells::Slice(b))
+            },
+            Cells::Uniform(cell, len) => (Cells::Uniform(cell, n), Cells::Uniform(cell, len - n)),
+            Cells::Palette(palette, range) => (Cells::Palette(palette, range.start..range.start + n), Cells::Palette(palette, range.start + n..range.end)),
+        }
+    }
+
+    fn iter(self) -> CellsIter<'a, T> {
+        match self {
+            Cells::Slice(cells) => CellsIter::Slice(cells.iter()),
+            Cells::Uniform(cell, len) => CellsIter::Uniform(std::iter::repeat_n(cell, len)),
+            Cells::Palette(palette, range) => CellsIter::Palette(palette, range),
+        }
+    }
+}
+
+impl<'a, T> Iterator for CellsIter<'a, T> {
+    type Item = &'a Option<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            CellsIter::Slice(iter) => iter.next(),
+            CellsIter::Uniform(iter) => iter.next(),
+            CellsIter::Palette(palette, range) => range.next().map(|local|palette.get(local)),
+        }
+    }
+}
+
+/// Shared state of `RectIter` and `RectIterMut`
+struct Rect<S, I, const N: usize> {
+    min:(i64, i64),
+    max:(i64, i64),
+    rows:std::vec::IntoIter<RectRow<S>>,
+    row:Option<RectRow<S>>,
+    y:i64,
+    cursor:usize,
+    segment:Option<(i64, I)>,
+}
+
+impl<S, I, const N: usize> Rect<S, I, N> {
+    fn new(min:(i32, i32), max:(i32, i32), mut chunks:Vec<(ChunkIndex, S)>) -> Self {
+        chunks.sort_by_key(|(chunk_index, _)|(chunk_index.y, chunk_index.x));
+        let mut rows:Vec<RectRow<S>> = Vec::new();
+        for (chunk_index, cells) in chunks {
+            let (left, top):(i32, i32) = chunk_index.index::<N>().into();
+            let chunk = RectChunk { left:left as i64, offset:0, rest:cells };
+            match rows.last_mut() {
+                Some(row) if row.top == top as i64 => row.chunks.push(chunk),
+                _ => rows.push(RectRow { top:top as i64, chunks:vec![chunk] }),
+            }
+        }
+        Self {
+            min:(min.0 as i64, min.1 as i64),
+            max:(max.0 as i64, max.1 as i64),
+            rows:rows.into_iter(),
+            row:None,
+            y:0,
+            cursor:0,
+            segment:None,
+        }
+    }
+
+    /// Advances to the next segment, i.e. the in-range cells of a single chunk within a single row.
+    ///
+    /// `split` splits off the first `n` cells of the remaining cells of a chunk
+    fn next_segment(&mut self, split:impl Fn(S, usize) -> (S, S), iter:impl Fn(S) -> I) -> Option<()> where S:Default {
+        loop {
+            if let Some(row) = self.row.as_mut() {
+                if let Some(chunk) = row.chunks.get_mut(self.cursor) {
+                    self.cursor += 1;
+                    let x_start = chunk.left.max(self.min.0);
+                    let x_end = (chunk.left + N as i64 - 1).min(self.max.0);
+                    let start = (self.y - row.top) as usize * N + (x_start - chunk.left) as usize;
+                    let len = (x_end - x_start + 1) as usize;
+                    let (_, rest) = split(std::mem::take(&mut chunk.rest), start - chunk.offset);
+                    let (segment, rest) = split(rest, len);
+                    chunk.rest = rest;
+                    chunk.offset = start + len;
+                    self.segment = Some((x_start, iter(segment)));
+                    return Some(());
+                }
+                if self.y < (row.top + N as i64 - 1).min(self.max.1) {
+                    self.y += 1;
+                    self.cursor = 0;
+                    continue;
+                }
+            }
+            let row = self.rows.next()?;
+            self.y = row.top.max(self.min.1);
+            self.cursor = 0;
+            self.row = Some(row);
+        }
+    }
+}
+
+/// Iterator over the cells within a rectangle of a `Grid` in row-major order, created by `Grid::iter_rect`
+pub struct RectIter<'a, T, const N: usize = CHUNK_SIZE>(Rect<Cells<'a, T>, CellsIter<'a, T>, N>);
+
+/// Mutable iterator over the cells within a rectangle of a `Grid` in row-major order, created by `Grid::iter_rect_mut`
+pub struct RectIterMut<'a, T, const N: usize = CHUNK_SIZE>(Rect<&'a mut [Option<T>], std::slice::IterMut<'a, Option<T>>, N>);
+
+impl<'a, T, const N: usize> Iterator for RectIter<'a, T, N> {
+    type Item = ((i32, i32), &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((x, segment)) = self.0.segment.as_mut() {
+                for cell in segment.by_ref() {
+                    let index = (*x as i32, self.0.y as i32);
+                    *x += 1;
+                    if let Some(cell) = cell {
+                        return Some((index, cell));
+                    }
+                }
+            }
+            self.0.next_segment(|s, n|s.split_at(n), |s|s.iter())?;
+        }
+    }
+}
+
+impl<'a, T, const N: usize> Iterator for RectIterMut<'a, T, N> {
+    type Item = ((i32, i32), &'a mut T);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((x, segment)) = self.0.segment.as_mut() {
+                for cell in segment.by_ref() {
+                    let index = (*x as i32, self.0.y as i32);
+                    *x += 1;
+                    if let Some(cell) = cell {
+                        return Some((index, cell));
+                    }
+                }
+            }
+            self.0.next_segment(|s, n|s.split_at_mut(n), |s|s.iter_mut())?;
+        }
+    }
+}
+
+/// Sorts `min` and `max` such that `min` is the top left and `max` is the bottom right corner
+pub(crate) fn corners(min:(i32, i32), max:(i32, i32)) -> ((i32, i32), (i32, i32)) {
+    ((min.0.min(max.0), min.1.min(max.1)), (min.0.max(max.0), min.1.max(max.1)))
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Iterates over all cells within the rectangle spanned by `min` and `max` (both inclusive) in row-major order
+    ///
+    /// Only the chunks overlapping the rectangle are visited
+    pub fn iter_rect(&self, min:impl Into<(i32, i32)>, max:impl Into<(i32, i32)>) -> RectIter<'_, T, N> {
+        let (min, max) = corners(min.into(), max.into());
+        let (c0, c1) = (Index::from(min).chunk_index::<N>(), Index::from(max).chunk_index::<N>());
+        let area = (c1.x - c0.x) as u64 + 1;
+        let area = area * ((c1.y - c0.y) as u64 + 1);
+        let in_range = |chunk_index:&ChunkIndex| (c0.x..=c1.x).contains(&chunk_index.x) && (c0.y..=c1.y).contains(&chunk_index.y);
+        let chunks:Vec<(ChunkIndex, Cells<T>)> = if area <= self.chunks.len() as u64 {
+            (c0.y..=c1.y)
+                .flat_map(|y|(c0.x..=c1.x).map(move |x|ChunkIndex { x, y }))
+                .filter_map(|chunk_index|self.chunks.get(&chunk_index).map(|chunk|(chunk_index, chunk)))
+                .filter(|(_, chunk)|!chunk.is_empty())
+                .map(|(chunk_index, chunk)|(chunk_index, Cells::of(chunk)))
+                .collect()
+        } else {
+            self.chunks.iter()
+                .filter(|(chunk_index, chunk)|in_range(chunk_index) && !chunk.is_empty())
+                .map(|(chunk_index, chunk)|(*chunk_index, Cells::of(chunk)))
+                .collect()
+        };
+        RectIter(Rect::new(min, max, chunks))
+    }
+
+    /// Iterates mutably over all cells within the rectangle spanned by `min` and `max` (both inclusive) in row-major order
+    ///
+    /// Only the chunks overlapping the rectangle are visited.
+    /// Uniform and palette chunks overlapping the rectangle are expanded, even if none of their cells is changed, see `Grid::iter_mut`
+    pub fn iter_rect_mut(&mut self, min:impl Into<(i32, i32)>, max:impl Into<(i32, i32)>) -> RectIterMut<'_, T, N> {
+        let (min, max) = corners(min.into(), max.into());
+        let (c0, c1) = (Index::from(min).chunk_index::<N>(), Index::from(max).chunk_index::<N>());
+        let in_range = |chunk_index:&ChunkIndex| (c0.x..=c1.x).contains(&chunk_index.x) && (c0.y..=c1.y).contains(&chunk_index.y);
+        let chunks:Vec<(ChunkIndex, &mut [Option<T>])> = self.chunks.iter_mut()
+            .filter(|(chunk_index, chunk)|in_range(chunk_index) && !chunk.is_empty())
+            .map(|(chunk_index, chunk)|{
+                let chunk = make_flat(chunk);
+                (*chunk_index, chunk.inner.as_mut_slice())
+            })
+            .collect();
+        self.dirty.extend(chunks.iter().map(|(chunk_index, _)|*chunk_index));
+        RectIterMut(Rect::new(min, max, chunks))
+    }
+}
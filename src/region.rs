@@ -0,0 +1,333 @@
+use std::{collections::HashMap, fs::{self, File, OpenOptions}, io::{self, Read, Seek, SeekFrom, Write}, path::PathBuf};
+use serde::{de::DeserializeOwned, Serialize};
+use crate::{Chunk, ChunkStore, Compact};
+
+/// Magic bytes at the start of every region file
+const MAGIC:[u8; 4] = *b"EGRF";
+
+/// Version of the region file format written by `RegionFile`
+pub const REGION_VERSION:u32 = 1;
+
+/// Default width and height of a region in chunks
+pub const REGION_SIZE:u32 = 32;
+
+/// Largest width and height of a region in chunks accepted by `RegionFile`
+pub const MAX_REGION_SIZE:u32 = 256;
+
+/// Default number of region files a `RegionStore` keeps open
+pub const MAX_OPEN_REGIONS:usize = 16;
+
+/// Size of the header preceding the offset table
+const HEADER_LEN:u64 = 24;
+
+/// Size of an entry of the offset table
+const SLOT_LEN:u64 = 16;
+
+/// Location of a chunk record within a region file, `len == 0` if the slot is empty
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    offset:u64,
+    len:u32,
+    capacity:u32,
+}
+
+/// A file storing the chunks of a square region of `region_size * region_size` chunks
+///
+/// The file starts with a header holding the format version, chunk size, region size and region coordinate,
+/// followed by a table with the offset and length of every chunk record.
+/// Records are encoded using bincode in their `Compact` form and are overwritten in place if they still fit,
+/// otherwise they are moved to the end of the file, so saving a chunk does not rewrite the rest of the region.
+/// Requires the `file-store` feature
+pub struct RegionFile<F = File> {
+    file:F,
+    chunk_size:u32,
+    region_size:u32,
+    region:(i32, i32),
+    table:Vec<Slot>,
+    end:u64,
+}
+
+fn invalid(msg:impl Into<String>) -> bincode::Error {
+    Box::new(bincode::ErrorKind::Custom(msg.into()))
+}
+
+/// Gets the number of slots in the offset table of a region, failing if the region size is 0 or larger than `MAX_REGION_SIZE`
+fn slot_count(region_size:u32) -> bincode::Result<usize> {
+    if region_size == 0 || region_size > MAX_REGION_SIZE {
+        return Err(invalid(format!("region size {} is not within 1..={}", region_size, MAX_REGION_SIZE)));
+    }
+    region_size.checked_mul(region_size).map(|x|x as usize).ok_or_else(|| invalid("region size too large"))
+}
+
+impl<F:Read + Write + Seek> RegionFile<F> {
+    /// Creates an empty region in `file` holding the chunks of the region at the region coordinate, overwriting its header and offset table
+    /// 
+    /// The region covers the chunks from `region * region_size` up to but excluding `(region + 1) * region_size`.
+    /// Fails if `region_size` is 0 or larger than `MAX_REGION_SIZE`
+    pub fn create(mut file:F, chunk_size:u32, region:(i32, i32), region_size:u32) -> bincode::Result<Self> {
+        let table = vec![Slot::default(); slot_count(region_size)?];
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&REGION_VERSION.to_le_bytes());
+        header.extend_from_slice(&chunk_size.to_le_bytes());
+        header.extend_from_slice(&region_size.to_le_bytes());
+        header.extend_from_slice(&region.0.to_le_bytes());
+        header.extend_from_slice(&region.1.to_le_bytes());
+        header.resize((HEADER_LEN + SLOT_LEN * table.len() as u64) as usize, 0);
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)?;
+        Ok(Self { file, chunk_size, region_size, region, end:header.len() as u64, table })
+    }
+
+    /// Opens an existing region in `file`, validating its header
+    pub fn open(mut file:F) -> bincode::Result<Self> {
+        let mut header = [0; HEADER_LEN as usize];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+        let word = |i:usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        if header[0..4] != MAGIC {
+            return Err(invalid("not a region file"));
+        }
+        if word(4) != REGION_VERSION {
+            return Err(invalid(format!("unsupported region file version {}", word(4))));
+        }
+        let (chunk_size, region_size) = (word(8), word(12));
+        let region = (word(16) as i32, word(20) as i32);
+        let mut bytes = vec![0; slot_count(region_size)? * SLOT_LEN as usize];
+        file.read_exact(&mut bytes)?;
+        let table:Vec<Slot> = bytes.chunks_exact(SLOT_LEN as usize).map(|x|Slot {
+            offset:u64::from_le_bytes(x[0..8].try_into().unwrap()),
+            len:u32::from_le_bytes(x[8..12].try_into().unwrap()),
+            capacity:u32::from_le_bytes(x[12..16].try_into().unwrap()),
+        }).collect();
+        let end = table.iter()
+            .map(|x|x.offset + x.capacity as u64)
+            .fold(HEADER_LEN + bytes.len() as u64, u64::max);
+        Ok(Self { file, chunk_size, region_size, region, table, end })
+    }
+
+    /// Gets the width and height of a chunk stored in the region
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    /// Gets the width and height of the region in chunks
+    pub fn region_size(&self) -> u32 {
+        self.region_size
+    }
+
+    /// Gets the coordinate of the region, see `RegionStore::region_of`
+    pub fn region(&self) -> (i32, i32) {
+        self.region
+    }
+
+    /// Returns `true` if a chunk is stored at the chunk coordinate, which is `false` for chunks outside of the region
+    pub fn contains_chunk(&self, coord:(i32, i32)) -> bool {
+        self.slot(coord).is_ok_and(|i|self.table[i].len > 0)
+    }
+
+    /// Reads the chunk at the chunk coordinate, or `None` if no chunk is stored there
+    /// 
+    /// Fails if the chunk lies outside of the region
+    pub fn read_chunk<T:DeserializeOwned + Clone, const N: usize>(&mut self, coord:(i32, i32)) -> bincode::Result<Option<Chunk<T, N>>> {
+        self.check_size::<N>()?;
+        let slot = self.table[self.slot(coord)?];
+        if slot.len == 0 {
+            return Ok(None);
+        }
+        let mut bytes = vec![0; slot.len as usize];
+        self.file.seek(SeekFrom::Start(slot.offset))?;
+        self.file.read_exact(&mut bytes)?;
+        let Compact(chunk):Compact<Chunk<T, N>> = bincode::deserialize(&bytes)?;
+        if chunk.coord() != coord {
+            return Err(invalid(format!("chunk stored at {:?} has coordinate {:?}", coord, chunk.coord())));
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Writes the chunk into its slot, replacing any chunk stored there
+    ///
+    /// Only the record of the chunk and its entry in the offset table are written.
+    /// Fails if the chunk lies outside of the region
+    pub fn write_chunk<T:Serialize, const N: usize>(&mut self, chunk:&Chunk<T, N>) -> bincode::Result<()> {
+        self.check_size::<N>()?;
+        let i = self.slot(chunk.coord())?;
+        let bytes = bincode::serialize(&Compact(chunk))?;
+        let len = u32::try_from(bytes.len()).map_err(|_|invalid("chunk record too large"))?;
+        let mut slot = self.table[i];
+        if slot.capacity < len {
+            // leave room for the chunk to grow a bit before it has to move again
+            slot = Slot { offset:self.end, len, capacity:len.saturating_add(len / 4) };
+            self.end += slot.capacity as u64;
+        }
+        slot.len = len;
+        self.file.seek(SeekFrom::Start(slot.offset))?;
+        self.file.write_all(&bytes)?;
+        self.write_slot(i, slot)
+    }
+
+    /// Removes the chunk stored at the chunk coordinate, if any
+    ///
+    /// The space of the record is kept and reused if a chunk is written to the slot again.
+    /// Fails if the chunk lies outside of the region
+    pub fn remove_chunk(&mut self, coord:(i32, i32)) -> bincode::Result<()> {
+        let i = self.slot(coord)?;
+        let slot = self.table[i];
+        if slot.len == 0 {
+            return Ok(());
+        }
+        self.write_slot(i, Slot { len:0, ..slot })
+    }
+
+    /// Flushes the underlying file
+    pub fn flush(&mut self) -> bincode::Result<()> {
+        Ok(self.file.flush()?)
+    }
+
+    /// Consumes the region, returning the underlying file
+    pub fn into_inner(self) -> F {
+        self.file
+    }
+
+    fn check_size<const N: usize>(&self) -> bincode::Result<()> {
+        if self.chunk_size as usize != N {
+            return Err(invalid(format!("region stores chunks of size {}, not {}", self.chunk_size, N)));
+        }
+        Ok(())
+    }
+
+    /// Gets the slot of the chunk at the chunk coordinate, failing if the chunk lies outside of the region
+    fn slot(&self, coord:(i32, i32)) -> bincode::Result<usize> {
+        let size = self.region_size as i32;
+        if (coord.0.div_euclid(size), coord.1.div_euclid(size)) != self.region {
+            return Err(invalid(format!("chunk {:?} lies outside of region {:?}", coord, self.region)));
+        }
+        let (x, y) = (coord.0.rem_euclid(size), coord.1.rem_euclid(size));
+        Ok((y * size + x) as usize)
+    }
+
+    fn write_slot(&mut self, i:usize, slot:Slot) -> bincode::Result<()> {
+        let mut bytes = [0; SLOT_LEN as usize];
+        bytes[0..8].copy_from_slice(&slot.offset.to_le_bytes());
+        bytes[8..12].copy_from_slice(&slot.len.to_le_bytes());
+        bytes[12..16].copy_from_slice(&slot.capacity.to_le_bytes());
+        self.file.seek(SeekFrom::Start(HEADER_LEN + SLOT_LEN * i as u64))?;
+        self.file.write_all(&bytes)?;
+        self.table[i] = slot;
+        Ok(())
+    }
+}
+
+/// A `ChunkStore` grouping chunks into `RegionFile`s in a directory
+///
+/// Region files are opened on first access and kept open, up to `max_open_regions` files after which the least recently used file is closed
+pub struct RegionStore {
+    dir:PathBuf,
+    region_size:u32,
+    /// Open region files and the tick they were last used at
+    regions:HashMap<(i32, i32), (RegionFile, u64)>,
+    tick:u64,
+    max_open:usize,
+}
+
+impl RegionStore {
+    /// Creates a store in `dir` with regions of `REGION_SIZE * REGION_SIZE` chunks, creating the directory if it does not exist
+    pub fn new(dir:impl Into<PathBuf>) -> io::Result<Self> {
+        Self::with_region_size(dir, REGION_SIZE)
+    }
+
+    /// Creates a store in `dir` with regions of `region_size * region_size` chunks, creating the directory if it does not exist
+    ///
+    /// `region_size` is clamped to `1..=MAX_REGION_SIZE`.
+    /// Existing region files keep the region size they were created with and must match `region_size`
+    pub fn with_region_size(dir:impl Into<PathBuf>, region_size:u32) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, region_size:region_size.clamp(1, MAX_REGION_SIZE), regions:HashMap::new(), tick:0, max_open:MAX_OPEN_REGIONS })
+    }
+
+    /// Gets the maximum number of region files kept open
+    pub fn max_open_regions(&self) -> usize {
+        self.max_open
+    }
+
+    /// Sets the maximum number of region files kept open, which is at least 1
+    /// 
+    /// Files over the limit are closed on the next access
+    pub fn set_max_open_regions(&mut self, max_open:usize) {
+        self.max_open = max_open.max(1);
+    }
+
+    /// Gets the number of region files currently open
+    pub fn open_regions(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Gets the path of the region file with the region coordinate
+    pub fn path(&self, region:(i32, i32)) -> PathBuf {
+        self.dir.join(format!("r.{}.{}.region", region.0, region.1))
+    }
+
+    /// Gets the coordinate of the region containing the chunk at the chunk coordinate
+    pub fn region_of(&self, coord:(i32, i32)) -> (i32, i32) {
+        let size = self.region_size as i32;
+        (coord.0.div_euclid(size), coord.1.div_euclid(size))
+    }
+
+    /// Gets the region file containing the chunk, or `None` if it does not exist and `create` is `false`
+    fn region<const N: usize>(&mut self, coord:(i32, i32), create:bool) -> bincode::Result<Option<&mut RegionFile>> {
+        let region = self.region_of(coord);
+        self.tick += 1;
+        if !self.regions.contains_key(&region) {
+            let path = self.path(region);
+            let file = if path.exists() {
+                RegionFile::open(OpenOptions::new().read(true).write(true).open(&path)?)?
+            } else if create {
+                let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+                RegionFile::create(file, N as u32, region, self.region_size)?
+            } else {
+                return Ok(None);
+            };
+            if file.region_size() != self.region_size {
+                return Err(invalid(format!("region file {:?} has region size {}", path, file.region_size())));
+            }
+            if file.region() != region {
+                return Err(invalid(format!("region file {:?} holds region {:?}", path, file.region())));
+            }
+            self.regions.insert(region, (file, self.tick));
+        }
+        if let Some((_, tick)) = self.regions.get_mut(&region) {
+            *tick = self.tick;
+        }
+        // the region just used has the latest tick and is never closed
+        while self.regions.len() > self.max_open {
+            let lru = self.regions.iter().min_by_key(|(_, (_, tick))|*tick).map(|(region, _)|*region).unwrap();
+            let (mut file, _) = self.regions.remove(&lru).unwrap();
+            file.flush()?;
+        }
+        Ok(self.regions.get_mut(&region).map(|(file, _)|file))
+    }
+}
+
+impl<T:Serialize + DeserializeOwned + Clone, const N: usize> ChunkStore<T, N> for RegionStore {
+    type Error = bincode::Error;
+
+    fn load(&mut self, coord:(i32, i32)) -> Result<Option<Chunk<T, N>>, Self::Error> {
+        match self.region::<N>(coord, false)? {
+            Some(region) => region.read_chunk(coord),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&mut self, chunk:&Chunk<T, N>) -> Result<(), Self::Error> {
+        let region = self.region::<N>(chunk.coord(), true)?.unwrap();
+        region.write_chunk(chunk)
+    }
+
+    fn remove(&mut self, coord:(i32, i32)) -> Result<(), Self::Error> {
+        match self.region::<N>(coord, false)? {
+            Some(region) => region.remove_chunk(coord),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::{collections::HashSet, sync::Arc};
+#[cfg(feature = "file-store")]
+use std::{fs, io, path::PathBuf};
+#[cfg(feature = "file-store")]
+use serde::{de::DeserializeOwned, Serialize};
+use crate::{make_mut, Chunk, ChunkIndex, Grid, CHUNK_SIZE};
+#[cfg(feature = "file-store")]
+use crate::Compact;
+
+/// Persistent storage of chunks used by `StreamingGrid`
+pub trait ChunkStore<T, const N: usize = CHUNK_SIZE> {
+    type Error;
+
+    /// Loads the chunk at the chunk coordinate, or `None` if no chunk has been stored there
+    fn load(&mut self, coord:(i32, i32)) -> Result<Option<Chunk<T, N>>, Self::Error>;
+
+    /// Stores the chunk at its own coordinate, replacing any previously stored chunk
+    fn save(&mut self, chunk:&Chunk<T, N>) -> Result<(), Self::Error>;
+
+    /// Removes the chunk stored at the chunk coordinate, if any
+    fn remove(&mut self, coord:(i32, i32)) -> Result<(), Self::Error>;
+}
+
+/// A `ChunkStore` keeping one file per chunk in a directory, encoded using bincode in their `Compact` form
+/// 
+/// Requires the `file-store` feature
+#[cfg(feature = "file-store")]
+pub struct FileChunkStore {
+    dir:PathBuf,
+}
+
+#[cfg(feature = "file-store")]
+impl FileChunkStore {
+    /// Creates a store in `dir`, creating the directory if it does not exist
+    pub fn new(dir:impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Gets the path of the file holding the chunk at the chunk coordinate
+    pub fn path(&self, coord:(i32, i32)) -> PathBuf {
+        self.dir.join(format!("{}_{}.chunk", coord.0, coord.1))
+    }
+}
+
+#[cfg(feature = "file-store")]
+impl<T:Serialize + DeserializeOwned + Clone, const N: usize> ChunkStore<T, N> for FileChunkStore {
+    type Error = bincode::Error;
+
+    fn load(&mut self, coord:(i32, i32)) -> Result<Option<Chunk<T, N>>, Self::Error> {
+        let bytes = match fs::read(self.path(coord)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let Compact(chunk):Compact<Chunk<T, N>> = bincode::deserialize(&bytes)?;
+        if chunk.coord() != coord {
+            return Err(Box::new(bincode::ErrorKind::Custom(format!("chunk stored at {:?} has coordinate {:?}", coord, chunk.coord()))));
+        }
+        Ok(Some(chunk))
+    }
+
+    fn save(&mut self, chunk:&Chunk<T, N>) -> Result<(), Self::Error> {
+        fs::write(self.path(chunk.coord()), bincode::serialize(&Compact(chunk))?)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, coord:(i32, i32)) -> Result<(), Self::Error> {
+        match fs::remove_file(self.path(coord)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Change of the resident chunks of a `StreamingGrid`, passed to the callback of `StreamingGrid::update_with`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// The chunk at the coordinate has been loaded from the store, or found missing in the store
+    Loaded((i32, i32)),
+
+    /// The chunk at the coordinate is about to be saved and removed from the grid
+    Unloaded((i32, i32)),
+}
+
+/// A `Grid` keeping only the chunks near one or more focus points in memory
+///
+/// Chunks coming into range are loaded from a `ChunkStore` and chunks going out of range are saved to it and evicted.
+/// Only chunks modified or generated since they were loaded or last saved are written back, chunks which became empty are removed from the store
+/// unless they were generated, see `Grid::ensure_chunk`.
+/// Modified chunks are tracked independently of the dirty flags of the grid, which are left to the user.
+/// Loading and evicting chunks does not mark them dirty, and evicted chunks are no longer reported as dirty,
+/// so changes should be collected using `Grid::take_changes` before calling `update`
+pub struct StreamingGrid<T, S, const N: usize = CHUNK_SIZE> {
+    grid:Grid<T, N>,
+    store:S,
+    radius:u32,
+    resident:HashSet<(i32, i32)>,
+}
+
+impl<T, S:ChunkStore<T, N>, const N: usize> StreamingGrid<T, S, N> {
+    /// Creates an empty streaming grid keeping the chunks within `radius` chunks of the focus points resident
+    pub fn new(store:S, radius:u32) -> Self {
+        let mut grid = Grid::default();
+        grid.dirty.unsaved = Some(HashSet::new());
+        Self { grid, store, radius, resident:HashSet::new() }
+    }
+
+    /// Gets the resident part of the grid
+    pub fn grid(&self) -> &Grid<T, N> {
+        &self.grid
+    }
+
+    /// Gets the resident part of the grid mutably
+    ///
+    /// Chunks created outside of the resident area are merged with the stored chunk, saved and evicted on the next `update`
+    pub fn grid_mut(&mut self) -> &mut Grid<T, N> {
+        &mut self.grid
+    }
+
+    /// Gets the store backing the grid
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Gets the store backing the grid mutably
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    /// Gets the radius in chunks around the focus points
+    pub fn radius(&self) -> u32 {
+        self.radius
+    }
+
+    /// Sets the radius in chunks around the focus points, which takes effect on the next `update`
+    pub fn set_radius(&mut self, radius:u32) {
+        self.radius = radius;
+    }
+
+    /// Returns `true` if the chunk at the chunk coordinate has been loaded, whether or not the store held a chunk there
+    pub fn is_resident(&self, coord:impl Into<(i32, i32)>) -> bool {
+        self.resident.contains(&coord.into())
+    }
+
+    /// Loads the chunk at the chunk coordinate if it is not yet resident
+    ///
+    /// If the chunk is already present in the grid, the stored cells are merged underneath the cells present in the grid.
+    /// The chunk is evicted again on the next `update` if it is out of range.
+    pub fn ensure_loaded(&mut self, coord:impl Into<(i32, i32)>) -> Result<(), S::Error> {
+        self.load(coord.into())
+    }
+
+    /// Gets a immutable reference to `T`, loading its chunk if needed
+    pub fn get_or_load(&mut self, index:impl Into<(i32, i32)>) -> Result<Option<&T>, S::Error> {
+        let index:(i32, i32) = index.into();
+        self.load(self.grid.chunk_of(index))?;
+        Ok(self.grid.get(index))
+    }
+
+    /// Gets an mutable reference to `T`, loading its chunk if needed
+    pub fn get_or_load_mut(&mut self, index:impl Into<(i32, i32)>) -> Result<Option<&mut T>, S::Error> {
+        let index:(i32, i32) = index.into();
+        self.load(self.grid.chunk_of(index))?;
+        Ok(self.grid.get_mut(index))
+    }
+
+    /// Loads all chunks within range of the `focus` cells and evicts all other chunks, see `update_with`
+    pub fn update(&mut self, focus:impl IntoIterator<Item = (i32, i32)>) -> Result<(), S::Error> {
+        self.update_with(focus, |_|{})
+    }
+
+    /// Loads all chunks within range of the `focus` cells and evicts all other chunks, calling `F` for each chunk loaded or evicted
+    pub fn update_with<F:FnMut(StreamEvent)>(&mut self, focus:impl IntoIterator<Item = (i32, i32)>, mut f:F) -> Result<(), S::Error> {
+        let radius = self.radius as i64;
+        let mut wanted = HashSet::new();
+        for index in focus {
+            let (cx, cy) = self.grid.chunk_of(index);
+            for y in cy as i64 - radius..=cy as i64 + radius {
+                for x in cx as i64 - radius..=cx as i64 + radius {
+                    if let (Ok(x), Ok(y)) = (i32::try_from(x), i32::try_from(y)) {
+                        wanted.insert((x, y));
+                    }
+                }
+            }
+        }
+
+        let mut evict:Vec<(i32, i32)> = self.resident.iter()
+            .copied()
+            .chain(self.grid.chunks.values().map(|chunk|chunk.coord()))
+            .filter(|coord|!wanted.contains(coord))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        evict.sort_unstable();
+        for coord in evict {
+            f(StreamEvent::Unloaded(coord));
+            self.unload(coord)?;
+        }
+
+        let mut load:Vec<(i32, i32)> = wanted.into_iter().filter(|coord|!self.resident.contains(coord)).collect();
+        load.sort_unstable();
+        for coord in load {
+            self.load(coord)?;
+            f(StreamEvent::Loaded(coord));
+        }
+        Ok(())
+    }
+
+    /// Saves all chunks modified since they were last saved without evicting them, chunks created outside of the resident area are loaded first
+    pub fn flush(&mut self) -> Result<(), S::Error> {
+        let created:Vec<(i32, i32)> = self.grid.chunks.values()
+            .map(|chunk|chunk.coord())
+            .filter(|coord|!self.resident.contains(coord))
+            .collect();
+        for coord in created {
+            self.load(coord)?;
+        }
+        let mut unsaved:Vec<ChunkIndex> = self.grid.dirty.unsaved.iter()
+            .flatten()
+            .copied()
+            .filter(|chunk_index|self.resident.contains(&chunk_index.coord::<N>()))
+            .collect();
+        unsaved.sort_unstable();
+        for chunk_index in unsaved {
+            self.save(chunk_index.coord::<N>())?;
+            self.unsaved().remove(&chunk_index);
+        }
+        Ok(())
+    }
+
+    /// Saves all modified chunks, returning the store
+    pub fn into_store(mut self) -> Result<S, S::Error> {
+        self.flush()?;
+        Ok(self.store)
+    }
+
+    fn load(&mut self, coord:(i32, i32)) -> Result<(), S::Error> {
+        if self.resident.contains(&coord) {
+            return Ok(());
+        }
+        if let Some(stored) = self.store.load(coord)? {
+            if stored.is_empty() {
+                // an empty record keeps a chunk left empty by the generator from being generated again
+                self.grid.generated.insert(stored.index);
+            }
+            let existing = ChunkIndex::from_coord::<N>(coord).and_then(|chunk_index|self.grid.chunks.get_mut(&chunk_index));
+            match existing {
+                // cells written before the chunk was loaded take precedence over the stored ones
+                Some(chunk) => {
+                    let chunk_index = chunk.index;
+                    let chunk = make_mut(chunk);
+                    let top_left = chunk.top_left();
+                    for (index, t) in stored {
+                        let local = (index.1 - top_left.1) as usize * N + (index.0 - top_left.0) as usize;
+                        if chunk.get_local(local).is_none_or(|x|x.is_none()) {
+                            chunk.insert(local, t);
+                        }
+                    }
+                    self.unsaved().insert(chunk_index);
+                },
+                None if !stored.is_empty() => {
+                    self.grid.chunks.insert(stored.index, Arc::new(stored));
+                },
+                None => {},
+            }
+        }
+        self.resident.insert(coord);
+        Ok(())
+    }
+
+    fn unload(&mut self, coord:(i32, i32)) -> Result<(), S::Error> {
+        // merge chunks created outside of the resident area with the stored chunk before overwriting it
+        self.load(coord)?;
+        if let Some(chunk_index) = ChunkIndex::from_coord::<N>(coord) {
+            if self.unsaved().contains(&chunk_index) {
+                self.save(coord)?;
+                self.unsaved().remove(&chunk_index);
+            }
+            self.grid.chunks.remove(&chunk_index);
+            self.grid.dirty.chunks.remove(&chunk_index);
+            self.grid.generated.remove(&chunk_index);
+        }
+        self.resident.remove(&coord);
+        Ok(())
+    }
+
+    /// Saves the chunk at the chunk coordinate, or removes it from the store if it is no longer present in the grid
+    ///
+    /// Generated chunks which are empty are stored as an empty chunk, such that they are not generated again once loaded
+    fn save(&mut self, coord:(i32, i32)) -> Result<(), S::Error> {
+        match self.grid.chunk(coord) {
+            Some(chunk) => self.store.save(chunk),
+            None if ChunkIndex::from_coord::<N>(coord).is_some_and(|chunk_index|self.grid.generated.contains(&chunk_index)) => {
+                self.store.save(&Chunk::new(coord))
+            },
+            None => self.store.remove(coord),
+        }
+    }
+
+    /// Gets the chunks modified since they were last saved
+    fn unsaved(&mut self) -> &mut HashSet<ChunkIndex> {
+        self.grid.dirty.unsaved.get_or_insert_with(HashSet::new)
+    }
+}
@@ -0,0 +1,122 @@
+use glam::Vec2;
+use crate::{Grid, RayHit, RayVisit, RectIter, RectIterMut};
+
+/// Maps between world space and the cells of a `Grid`
+///
+/// Cell `(0, 0)` spans from `origin` to `origin + cell_size` in world space
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridTransform {
+    /// World position of the top left corner of cell `(0, 0)`
+    pub origin:Vec2,
+
+    /// Width and height of a cell in world space
+    pub cell_size:Vec2,
+}
+
+impl Default for GridTransform {
+    fn default() -> Self {
+        Self { origin:Vec2::ZERO, cell_size:Vec2::ONE }
+    }
+}
+
+impl GridTransform {
+    /// Creates a transform with square cells of `cell_size`
+    pub fn new(origin:Vec2, cell_size:f32) -> Self {
+        Self { origin, cell_size:Vec2::splat(cell_size) }
+    }
+
+    /// Creates a transform with cells of `cell_size`, which need not be square
+    pub fn with_cell_size(origin:Vec2, cell_size:Vec2) -> Self {
+        Self { origin, cell_size }
+    }
+
+    /// Converts a world position into the position in cell space, where cell `(x, y)` spans from `(x, y)` to `(x + 1, y + 1)`
+    pub fn world_to_local(&self, world:Vec2) -> Vec2 {
+        (world - self.origin) / self.cell_size
+    }
+
+    /// Converts a position in cell space into a world position
+    pub fn local_to_world(&self, local:Vec2) -> Vec2 {
+        local * self.cell_size + self.origin
+    }
+
+    /// Gets the index of the cell containing the world position
+    pub fn world_to_cell(&self, world:Vec2) -> (i32, i32) {
+        let local = self.world_to_local(world).floor();
+        (local.x as i32, local.y as i32)
+    }
+
+    /// Gets the world position of the top left corner of the cell
+    pub fn cell_to_world(&self, index:impl Into<(i32, i32)>) -> Vec2 {
+        let index:(i32, i32) = index.into();
+        self.local_to_world(Vec2::new(index.0 as f32, index.1 as f32))
+    }
+
+    /// Gets the world position of the center of the cell
+    pub fn cell_center(&self, index:impl Into<(i32, i32)>) -> Vec2 {
+        self.cell_to_world(index) + self.cell_size * 0.5
+    }
+
+    /// Gets the world space rectangle covered by the cell as its top left and bottom right corner
+    pub fn cell_rect(&self, index:impl Into<(i32, i32)>) -> (Vec2, Vec2) {
+        let min = self.cell_to_world(index);
+        (min, min + self.cell_size)
+    }
+}
+
+impl<T, const N: usize> Grid<T, N> {
+    /// Gets a immutable reference to `T` at the world position
+    pub fn get_at_world(&self, transform:&GridTransform, world:Vec2) -> Option<&T> {
+        self.get(transform.world_to_cell(world))
+    }
+
+    /// Gets an mutable reference to `T` at the world position
+    pub fn get_at_world_mut(&mut self, transform:&GridTransform, world:Vec2) -> Option<&mut T> {
+        self.get_mut(transform.world_to_cell(world))
+    }
+
+    /// Iterates over all cells overlapping the world space rectangle spanned by `min` and `max` in row-major order, see `iter_rect`
+    pub fn iter_rect_world(&self, transform:&GridTransform, min:Vec2, max:Vec2) -> RectIter<'_, T, N> {
+        self.iter_rect(transform.world_to_cell(min), transform.world_to_cell(max))
+    }
+
+    /// Iterates mutably over all cells overlapping the world space rectangle spanned by `min` and `max` in row-major order, see `iter_rect_mut`
+    pub fn iter_rect_world_mut(&mut self, transform:&GridTransform, min:Vec2, max:Vec2) -> RectIterMut<'_, T, N> {
+        self.iter_rect_mut(transform.world_to_cell(min), transform.world_to_cell(max))
+    }
+
+    /// Casts a ray from `start` to `end` given in world space, see `cast_ray`
+    ///
+    /// `RayVisit::point`, `RayVisit::d` and `RayVisit::d_exit` are given in world space as well
+    pub fn cast_ray_world<F:FnMut(RayVisit<T>)->bool>(&self, transform:&GridTransform, start:Vec2, end:Vec2, mut f:F) {
+        let (local_start, local_end) = (transform.world_to_local(start), transform.world_to_local(end));
+        let scale = world_scale(start, end, local_start, local_end);
+        self.cast_ray(local_start, local_end, |mut visit|{
+            visit.point = transform.local_to_world(visit.point.into()).into();
+            visit.d *= scale;
+            visit.d_exit *= scale;
+            f(visit)
+        });
+    }
+
+    /// Casts a ray from `start` to `end` given in world space, see `raycast_first`
+    ///
+    /// `RayHit::point` and `RayHit::d` are given in world space as well
+    pub fn raycast_first_world<F:FnMut(&T)->bool>(&self, transform:&GridTransform, start:Vec2, end:Vec2, predicate:F) -> Option<RayHit<'_, T>> {
+        let (local_start, local_end) = (transform.world_to_local(start), transform.world_to_local(end));
+        let scale = world_scale(start, end, local_start, local_end);
+        let mut hit = self.raycast_first(local_start, local_end, predicate)?;
+        hit.point = transform.local_to_world(hit.point.into()).into();
+        hit.d *= scale;
+        Some(hit)
+    }
+}
+
+/// Gets the factor converting distances along the ray from cell space into world space
+fn world_scale(start:Vec2, end:Vec2, local_start:Vec2, local_end:Vec2) -> f32 {
+    let local = local_start.distance(local_end);
+    if local == 0.0 {
+        return 0.0;
+    }
+    start.distance(end) / local
+}
@@ -0,0 +1,187 @@
+use std::collections::{btree_map, BTreeMap, HashMap, VecDeque};
+use crate::{ChunkIndex, Grid, Index, CHUNK_SIZE};
+
+/// Values of the cells touched by a transaction before it started, grouped by chunk
+type Changes<T> = HashMap<ChunkIndex, BTreeMap<usize, Option<T>>>;
+
+/// A `Grid` recording edits such that they can be rolled back, undone and redone
+///
+/// Edits made between `begin` and `commit` form a single transaction on the undo stack.
+/// Edits made outside of a transaction are committed immediately, each as its own transaction.
+/// Only the first previous value of every touched cell is stored.
+pub struct UndoGrid<T, const N: usize = CHUNK_SIZE> {
+    grid:Grid<T, N>,
+    current:Changes<T>,
+    in_transaction:bool,
+    undo:VecDeque<Changes<T>>,
+    redo:Vec<Changes<T>>,
+    depth:usize,
+}
+
+impl<T:Clone, const N: usize> UndoGrid<T, N> {
+    /// Wraps `grid`, keeping at most `depth` transactions on the undo stack
+    pub fn new(grid:Grid<T, N>, depth:usize) -> Self {
+        Self { grid, current:HashMap::new(), in_transaction:false, undo:VecDeque::new(), redo:Vec::new(), depth }
+    }
+
+    /// Gets the wrapped grid
+    pub fn grid(&self) -> &Grid<T, N> {
+        &self.grid
+    }
+
+    /// Unwraps the grid, discarding the history and any open transaction
+    pub fn into_grid(self) -> Grid<T, N> {
+        self.grid
+    }
+
+    /// Gets the maximum number of transactions on the undo stack
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Sets the maximum number of transactions on the undo stack, dropping the oldest transactions if needed
+    pub fn set_depth(&mut self, depth:usize) {
+        self.depth = depth;
+        while self.undo.len() > depth {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Gets a immutable reference to `T`
+    pub fn get(&self, index:impl Into<(i32, i32)>) -> Option<&T> {
+        self.grid.get(index)
+    }
+
+    /// Gets an mutable reference to `T`, recording its current value
+    pub fn get_mut(&mut self, index:impl Into<(i32, i32)>) -> Option<&mut T> {
+        let index:(i32, i32) = index.into();
+        self.grid.get(index)?;
+        self.record(index);
+        self.auto_commit();
+        self.grid.get_mut(index)
+    }
+
+    /// Insert `T`, recording the previous value of the cell
+    pub fn insert(&mut self, index:impl Into<(i32, i32)>, t:T) {
+        let index:(i32, i32) = index.into();
+        self.record(index);
+        self.grid.insert(index, t);
+        self.auto_commit();
+    }
+
+    /// Remove `T`, recording it and returning it if it was present
+    pub fn remove(&mut self, index:impl Into<(i32, i32)>) -> Option<T> {
+        let index:(i32, i32) = index.into();
+        self.grid.get(index)?;
+        self.record(index);
+        let t = self.grid.remove(index);
+        self.auto_commit();
+        t
+    }
+
+    /// Returns `true` if a transaction has been started and not yet committed or rolled back
+    pub fn in_transaction(&self) -> bool {
+        self.in_transaction
+    }
+
+    /// Starts a transaction, does nothing if a transaction is already open
+    pub fn begin(&mut self) {
+        self.in_transaction = true;
+    }
+
+    /// Ends the open transaction and pushes it onto the undo stack, clearing the redo stack
+    ///
+    /// Transactions which did not touch any cell are dropped
+    pub fn commit(&mut self) {
+        self.in_transaction = false;
+        let changes = std::mem::take(&mut self.current);
+        if changes.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push_back(changes);
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Ends the open transaction, restoring every cell it touched
+    pub fn rollback(&mut self) {
+        self.in_transaction = false;
+        let changes = std::mem::take(&mut self.current);
+        self.apply(changes);
+    }
+
+    /// Returns `true` if there is a transaction to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns `true` if there is a transaction to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Reverts the last committed transaction, committing any open transaction first
+    ///
+    /// Returns `false` if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        self.commit();
+        let Some(changes) = self.undo.pop_back() else {
+            return false;
+        };
+        let changes = self.apply(changes);
+        self.redo.push(changes);
+        true
+    }
+
+    /// Reapplies the last undone transaction, committing any open transaction first
+    ///
+    /// Returns `false` if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        self.commit();
+        let Some(changes) = self.redo.pop() else {
+            return false;
+        };
+        let changes = self.apply(changes);
+        self.undo.push_back(changes);
+        true
+    }
+
+    /// Clears the undo and redo stacks
+    pub fn clear_history(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn record(&mut self, index:(i32, i32)) {
+        let i = Index::from(index);
+        let cells = self.current.entry(i.chunk_index::<N>()).or_default();
+        if let btree_map::Entry::Vacant(e) = cells.entry(i.local_index::<N>()) {
+            e.insert(self.grid.get(index).cloned());
+        }
+    }
+
+    fn auto_commit(&mut self) {
+        if !self.in_transaction {
+            self.commit();
+        }
+    }
+
+    /// Restores the recorded values, returning the values they replaced
+    fn apply(&mut self, changes:Changes<T>) -> Changes<T> {
+        let mut inverse:Changes<T> = HashMap::with_capacity(changes.len());
+        for (chunk_index, cells) in changes {
+            let top_left:(i32, i32) = chunk_index.index::<N>().into();
+            let replaced = inverse.entry(chunk_index).or_default();
+            for (local, t) in cells {
+                let index = (top_left.0 + (local % N) as i32, top_left.1 + (local / N) as i32);
+                replaced.insert(local, self.grid.remove(index));
+                if let Some(t) = t {
+                    self.grid.insert(index, t);
+                }
+            }
+        }
+        inverse
+    }
+}